@@ -0,0 +1,107 @@
+use crate::core::Vec3;
+use crate::interval::{Interval, IntervalWorldChoice};
+use crate::ray::Ray;
+
+// axis aligned bounding box, stored as one interval per axis
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    // the box is empty by default
+    pub fn empty() -> Self {
+        Self::new(
+            Interval::world_choice(IntervalWorldChoice::Empty),
+            Interval::world_choice(IntervalWorldChoice::Empty),
+            Interval::world_choice(IntervalWorldChoice::Empty),
+        )
+    }
+
+    // treat the two points as opposite corners, in any order
+    pub fn from_points(a: &Vec3<f64>, b: &Vec3<f64>) -> Self {
+        Self::new(
+            Interval::new(a.x().min(*b.x()), a.x().max(*b.x())),
+            Interval::new(a.y().min(*b.y()), a.y().max(*b.y())),
+            Interval::new(a.z().min(*b.z()), a.z().max(*b.z())),
+        )
+    }
+
+    // the smallest box enclosing both boxes
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Self::new(
+            Interval::enclosing(&a.x, &b.x),
+            Interval::enclosing(&a.y, &b.y),
+            Interval::enclosing(&a.z, &b.z),
+        )
+    }
+
+    // flat shapes (disks, quads) get a box with zero width along one axis, give it some thickness
+    // so the slab test still works
+    pub fn pad_to_minimums(&self) -> Self {
+        let delta = 0.0001;
+        let pad = |i: &Interval| if i.size() < delta { i.expand(delta) } else { *i };
+        Self::new(pad(&self.x), pad(&self.y), pad(&self.z))
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+
+    pub fn min(&self) -> Vec3<f64> {
+        Vec3::new(self.x.min(), self.y.min(), self.z.min())
+    }
+
+    pub fn max(&self) -> Vec3<f64> {
+        Vec3::new(self.x.max(), self.y.max(), self.z.max())
+    }
+
+    // slab test: on a hit, ray_t is narrowed down to the part of the ray inside the box
+    pub fn hit(&self, ray: &Ray, ray_t: &mut Interval) -> bool {
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+
+            // an empty slab (Aabb::empty, or the overlap of two boxes that do not meet) holds nothing,
+            // the swap below would otherwise turn it into the gap between its bounds
+            if ax.min() > ax.max() {
+                return false;
+            }
+
+            let adinv = 1.0 / ray.direction().axis(axis);
+            let origin = ray.origin().axis(axis);
+
+            let t0 = (ax.min() - origin) * adinv;
+            let t1 = (ax.max() - origin) * adinv;
+
+            // swap when the ray travels in the negative direction along this axis
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        *ray_t = Interval::new(t_min, t_max);
+        true
+    }
+}
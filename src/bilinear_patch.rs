@@ -0,0 +1,131 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// the surface swept between two opposite edges of a (possibly non-planar) quad:
+// p(u, v) = lerp(lerp(q00, q10, u), lerp(q01, q11, u), v)
+// corners are given in loop order q00, q10, q11, q01 like a mesh face, the front side faces along dp/du x dp/dv
+pub struct BilinearPatch {
+    corners: [Vec3<f64>; 4],
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl BilinearPatch {
+    pub fn new(q00: Vec3<f64>, q10: Vec3<f64>, q11: Vec3<f64>, q01: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        let corners = [q00, q10, q11, q01];
+        Self { bbox: patch_bounds(&corners), corners, mat }
+    }
+}
+
+impl Hittable for BilinearPatch {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let Some((t, u, v)) = intersect_patch(ray, &self.corners, interval) else {
+            return false;
+        };
+
+        fill_patch_hit(ray, &self.corners, t, u, v, hit_record);
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// the patch lies inside the convex hull of its corners
+pub(crate) fn patch_bounds(corners: &[Vec3<f64>; 4]) -> Aabb {
+    let [a, b, c, d] = corners;
+    Aabb::surrounding(&Aabb::from_points(a, c), &Aabb::from_points(b, d)).pad_to_minimums()
+}
+
+// reshetov's ray / bilinear patch test (ray tracing gems, "cool patches"): a quadratic in u,
+// then for each root the ray is intersected with the line segment across the patch at that u
+// returns the nearest (t, u, v) whose t lies strictly inside the interval
+pub(crate) fn intersect_patch(ray: &Ray, corners: &[Vec3<f64>; 4], interval: &Interval) -> Option<(f64, f64, f64)> {
+    let [q00, q10, q11, q01] = *corners;
+    let d = *ray.direction();
+
+    let e10 = q10 - q00;
+    let e11 = q11 - q10;
+    let e00 = q01 - q00;
+    let qn = e10.cross(&(q01 - q11));
+    let q00 = q00 - *ray.origin();
+    let q10 = q10 - *ray.origin();
+
+    // a + b u + c u^2 = 0, b is found from a + b + c to save a cross product
+    let a = q00.cross(&d).dot(&e00);
+    let c = qn.dot(&d);
+    let b = q10.cross(&d).dot(&e11) - (a + c);
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+
+    // numerically stable pair of roots, a flat patch (c = 0) leaves a linear equation
+    let roots = if c == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        [-a / b, -1.0]
+    } else {
+        let q = -0.5 * (b + root.copysign(b));
+        [q / c, if q != 0.0 { a / q } else { -1.0 }]
+    };
+
+    let mut best: Option<(f64, f64, f64)> = None;
+    for u in roots {
+        if !(0.0..=1.0).contains(&u) {
+            continue;
+        }
+
+        // segment across the patch at this u, from pa along pb
+        let pa = (1.0 - u) * q00 + u * q10;
+        let pb = (1.0 - u) * e00 + u * e11;
+        let n = d.cross(&pb);
+        let det = n.dot(&n);
+        if det == 0.0 {
+            continue;
+        }
+        let n = n.cross(&pa);
+        let t = n.dot(&pb) / det;
+        let v = n.dot(&d) / det;
+
+        if (0.0..=1.0).contains(&v) && interval.surrounds(t) && best.is_none_or(|(best_t, _, _)| t < best_t) {
+            best = Some((t, u, v));
+        }
+    }
+    best
+}
+
+// point, error bound, geometric normal and uv of a patch hit
+pub(crate) fn fill_patch_hit(ray: &Ray, corners: &[Vec3<f64>; 4], t: f64, u: f64, v: f64, hit_record: &mut HitRecord) {
+    let [q00, q10, q11, q01] = *corners;
+
+    let terms = [(1.0 - u) * (1.0 - v) * q00, u * (1.0 - v) * q10, u * v * q11, (1.0 - u) * v * q01];
+    hit_record.t = t;
+    hit_record.point = terms[0] + terms[1] + terms[2] + terms[3];
+    hit_record.error = gamma(10) * (terms[0].abs() + terms[1].abs() + terms[2].abs() + terms[3].abs());
+
+    let dpdu = (1.0 - v) * (q10 - q00) + v * (q11 - q01);
+    let dpdv = (1.0 - u) * (q01 - q00) + u * (q11 - q10);
+    let mut normal = dpdu.cross(&dpdv);
+
+    // a corner where two edges fold onto each other has no normal, borrow the diagonal one
+    if normal.dot(&normal) == 0.0 {
+        normal = (q11 - q00).cross(&(q01 - q10));
+    }
+    hit_record.set_face_normal(ray, &normal.normalize());
+    hit_record.tangent = dpdu;
+    hit_record.bitangent = dpdv;
+    hit_record.u = u;
+    hit_record.v = v;
+}
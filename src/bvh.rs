@@ -0,0 +1,110 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+// bounding volume hierarchy over primitives that are only known by index and bounding box
+// objects with many small parts (meshes, particle clouds) build one internally instead of
+// wrapping every part in its own Rc<dyn Hittable>
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>,      // primitive indices, each leaf owns a contiguous run
+}
+
+struct BvhNode {
+    bbox: Aabb,
+    start: usize,           // leaf: first entry in order; interior: index of the right child (left is next)
+    count: usize,           // number of primitives in a leaf, 0 for interior nodes
+}
+
+impl Bvh {
+    const MAX_LEAF_SIZE: usize = 4;
+
+    pub fn new(boxes: &[Aabb]) -> Self {
+        let mut bvh = Self { nodes: Vec::with_capacity(2 * boxes.len()), order: (0..boxes.len()).collect() };
+        if !boxes.is_empty() {
+            bvh.build(boxes, 0, boxes.len());
+        }
+        bvh
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |node| node.bbox)
+    }
+
+    // split order[start..end] at the median of the longest axis of the box centres
+    fn build(&mut self, boxes: &[Aabb], start: usize, end: usize) -> usize {
+        let mut bbox = Aabb::empty();
+        let mut centroid_box = Aabb::empty();
+        for &i in self.order[start..end].iter() {
+            bbox = Aabb::surrounding(&bbox, &boxes[i]);
+            let c = centroid(&boxes[i]);
+            centroid_box = Aabb::surrounding(&centroid_box, &Aabb::new(Interval::new(c[0], c[0]), Interval::new(c[1], c[1]), Interval::new(c[2], c[2])));
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bbox, start, count: end - start });
+
+        if end - start <= Self::MAX_LEAF_SIZE {
+            return node_index;
+        }
+
+        let axis = (0..3)
+            .max_by(|&a, &b| centroid_box.axis_interval(a).size().total_cmp(&centroid_box.axis_interval(b).size()))
+            .unwrap_or(0);
+
+        let mid = start + (end - start) / 2;
+        self.order[start..end].select_nth_unstable_by(mid - start, |&a, &b| centroid(&boxes[a])[axis].total_cmp(&centroid(&boxes[b])[axis]));
+
+        self.build(boxes, start, mid);
+        let right = self.build(boxes, mid, end);
+
+        self.nodes[node_index].start = right;
+        self.nodes[node_index].count = 0;
+        node_index
+    }
+
+    // walks the tree and calls hit_primitive for every primitive whose boxes the ray reaches
+    // hit_primitive returns the t of a hit inside the interval it is given, which then shrinks
+    // so only closer hits are reported afterwards; returns whether anything was hit
+    pub fn hit(&self, ray: &Ray, interval: &Interval, mut hit_primitive: impl FnMut(usize, &Interval) -> Option<f64>) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut closest = interval.max();
+        let mut hit_anything = false;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            let mut ray_t = Interval::new(interval.min(), closest);
+            if !node.bbox.hit(ray, &mut ray_t) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(index + 1);
+                continue;
+            }
+
+            for &primitive in self.order[node.start..node.start + node.count].iter() {
+                if let Some(t) = hit_primitive(primitive, &Interval::new(interval.min(), closest)) {
+                    closest = t;
+                    hit_anything = true;
+                }
+            }
+        }
+
+        hit_anything
+    }
+}
+
+fn centroid(bbox: &Aabb) -> [f64; 3] {
+    [
+        0.5 * (bbox.x.min() + bbox.x.max()),
+        0.5 * (bbox.y.min() + bbox.y.max()),
+        0.5 * (bbox.z.min() + bbox.z.max()),
+    ]
+}
@@ -1,9 +1,35 @@
-use crate::{degrees_to_radians, hittable::{self, HitRecord, Hittable}, random_float, Ray, INFINITY};
+use crate::{degrees_to_radians, hittable::{self, HitRecord, Hittable}, random_canonical, random_float, Ray, INFINITY};
 use crate::core::Vec3;
 use crate::interval::Interval;
 use crate::color::write_color;
 use std::cmp::Ordering;
 
+// what a ray that escapes the scene sees
+#[derive(Debug, Clone, Copy)]
+pub enum Background {
+    Solid(Vec3<f64>),
+    Gradient { horizon: Vec3<f64>, zenith: Vec3<f64> },    // blended by how far the ray points up
+    Black,                                                  // no light from outside, for closed or night scenes
+}
+
+impl Background {
+    // the old white to light blue sky
+    pub fn sky() -> Self {
+        Background::Gradient { horizon: Vec3::new(1.0, 1.0, 1.0), zenith: Vec3::new(0.5, 0.7, 1.0) }
+    }
+
+    pub fn value(&self, direction: &Vec3<f64>) -> Vec3<f64> {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { horizon, zenith } => {
+                let t = 0.5 * (direction.normalize().y() + 1.0);
+                (1.0 - t) * *horizon + t * *zenith
+            }
+            Background::Black => Vec3::origin(),
+        }
+    }
+}
+
 pub struct Camera{
     pub aspect_ratio: f64,
     pub image_width: u32,
@@ -15,6 +41,11 @@ pub struct Camera{
     pub lookat: Vec3<f64>,          // point the camera is looking at: TODO specify this as a direction: just put lookat at the centre of the screen or sm
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub shutter_open: f64,          // time the shutter opens, each camera ray gets a random time in [open, close]
+    pub shutter_close: f64,         // time the shutter closes, set equal to shutter_open to disable motion blur
+                                    // both have to lie in [0, 1] with open <= close, the time range moving objects are animated
+                                    // (and bounded) over; render checks this first and panics on anything else
+    pub background: Background,     // colour of rays that leave the scene, the only light unless something emits
 
 
     // private fields
@@ -24,6 +55,7 @@ pub struct Camera{
     pixel_00_origin_loc: Vec3<f64>, // Location of pixel 0,0
     pixel_delta_u: Vec3<f64>,       // Offset to pixel to the right
     pixel_delta_v: Vec3<f64>,       // Offset to pixel below
+    pixel_spread: f64,              // angle one pixel takes up, how fast the cone around a camera ray widens
     // camera frame basis vectros
     u: Vec3<f64>,
     v: Vec3<f64>,
@@ -49,12 +81,16 @@ impl Camera{
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0 as f64,
             focus_dist: 10 as f64,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            background: Background::sky(),
             centre: Vec3::origin(),
             image_height: 0,
             pixel_samples_scale: 0.5,
             pixel_00_origin_loc: Vec3::origin(),
             pixel_delta_u: Vec3::origin(),
             pixel_delta_v: Vec3::origin(),
+            pixel_spread: 0.0,
 
             // these dont do anything for now
             u: Vec3::origin(),
@@ -86,7 +122,7 @@ impl Camera{
                 for _k in 0..self.samples_per_pixel
                 {
                     let r = self.get_ray(i, j);
-                    pixel_color += Self::ray_color(&r,self.max_depth, world);
+                    pixel_color += self.ray_color(&r,self.max_depth, world);
                 }
                 image_data.push_str(&write_color(&(pixel_color * self.pixel_samples_scale)));
 
@@ -98,7 +134,7 @@ impl Camera{
 
     }
 
-    fn ray_color(ray: &Ray, depth: u32, world: &impl Hittable) -> Vec3<f64>
+    fn ray_color(&self, ray: &Ray, depth: u32, world: &impl Hittable) -> Vec3<f64>
     {
         // if depth is zero, return black to avoid infinite bounces
         if depth <= 0
@@ -110,35 +146,38 @@ impl Camera{
 
         // if we hit something, generate a new ray in a random direction and trace where it goes and do this recursively
 
-        // it is not set at 0.0 but at 0.0001 to prevent shadow acne (near hits)
-        if world.hit(ray, &Interval::new(0.0001, INFINITY), &mut hit_record)
+        // no epsilon on the near end: scattered rays start just outside the hit point's error bound
+        // (HitRecord::spawn_ray), so they cannot land on the surface they left (no shadow acne)
+        if world.hit(ray, &Interval::new(0.0, INFINITY), &mut hit_record)
         {
+            hit_record.set_footprint(ray);
+
             let mut attenuation = Vec3::origin();
             // ray scattered;
             let mut scattered = Ray::new(Vec3::origin(), Vec3::origin());
 
+            // whatever the surface gives off itself, black for non emissive materials
+            let emitted = hit_record.mat.emitted(ray, &hit_record);
+
             if hit_record.mat.scatter(ray, &hit_record, &mut attenuation, &mut scattered)
             {
-                return attenuation * Self::ray_color(&scattered, depth - 1, world);
+                return emitted + attenuation * self.ray_color(&scattered, depth - 1, world);
             }
 
-
-            // this is synonymous to black
-            return Vec3::origin();
+            return emitted;
         }
 
-
-        let unit_direction = ray.direction().normalize();
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        Vec3::new(
-            (1.0 - t) * 1.0 + t * 0.5, // Interpolating between white and light blue
-            (1.0 - t) * 1.0 + t * 0.7, // Interpolating between white and light blue
-            (1.0 - t) * 1.0 + t * 1.0, // Interpolating between white and light blue
-        )
+        self.background.value(ray.direction())
     }
 
     fn initialize(&mut self)
     {
+        // moving spheres and transforms only bound their motion between time 0 and time 1
+        assert!(
+            0.0 <= self.shutter_open && self.shutter_open <= self.shutter_close && self.shutter_close <= 1.0,
+            "the shutter has to open and close within [0, 1], got [{}, {}]", self.shutter_open, self.shutter_close
+        );
+
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as u32;
 
         self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
@@ -175,6 +214,7 @@ impl Camera{
         // calculate the horizontal and vertical delta vectors from pixel to pixel
         self.pixel_delta_u = viewport_u / self.image_width as f64;
         self.pixel_delta_v = viewport_v/ self.image_height as f64;
+        self.pixel_spread = 2.0 * h / self.image_height as f64;
 
         // calculate the location of the upper left pixel
         let viewport_00_origin = self.centre - (self.w * self.focus_dist)- viewport_u / 2.0 - viewport_v / 2.0;
@@ -206,8 +246,9 @@ impl Camera{
             false => self.defocus_disk_sample()
         };
         let ray_direction = pixel_sample - ray_origin;
+        let ray_time = self.shutter_open + random_canonical() * (self.shutter_close - self.shutter_open);
 
-        Ray::new(ray_origin, ray_direction)
+        Ray::new_with_time(ray_origin, ray_direction, ray_time).with_cone(0.0, self.pixel_spread)
     }
 
     fn sample_square() -> Vec3<f64>{
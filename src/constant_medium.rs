@@ -0,0 +1,89 @@
+use crate::aabb::Aabb;
+use crate::core::{Vec3, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::{Interval, IntervalWorldChoice};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::random_canonical;
+
+use std::rc::Rc;
+
+// a volume of constant density (fog, smoke) bounded by any closed hittable
+// a ray passing through it has a probability of scattering proportional to the distance travelled inside
+pub struct ConstantMedium {
+    boundary: Rc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Rc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Rc<dyn Hittable>, density: f64, albedo: Vec3<f64>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Rc::new(Isotropic::new(albedo)),
+        }
+    }
+
+    // use a custom phase function material instead of the isotropic one
+    pub fn with_phase_function(boundary: Rc<dyn Hittable>, density: f64, phase_function: Rc<dyn Material>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        // find where the ray enters the boundary, looking along the whole line so rays starting inside still count
+        if !self.boundary.hit(ray, &Interval::world_choice(IntervalWorldChoice::Universe), &mut rec1) {
+            return false;
+        }
+
+        // and where it leaves again
+        if !self.boundary.hit(ray, &Interval::new(rec1.t + 0.0001, INFINITY), &mut rec2) {
+            return false;
+        }
+
+        // only the part of the span inside the requested interval matters
+        let t_enter = rec1.t.max(interval.min());
+        let t_exit = rec2.t.min(interval.max());
+
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        // a ray starting inside the volume should not scatter behind its origin
+        let t_enter = t_enter.max(0.0);
+
+        let ray_length = ray.direction().mag();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        // exponential free flight, 1 - u keeps the log away from zero
+        let hit_distance = self.neg_inv_density * (1.0 - random_canonical()).ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        hit_record.t = t_enter + hit_distance / ray_length;
+        hit_record.point = ray.at(hit_record.t);
+        hit_record.error = Vec3::origin();    // no surface here to step off
+
+        // the normal and facing mean nothing inside a volume, any value works
+        hit_record.normal = Vec3::new(1.0, 0.0, 0.0);
+        hit_record.geometric_normal = hit_record.normal;
+        hit_record.front_face = true;
+        hit_record.mat = Rc::clone(&self.phase_function);
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{random_float, random_float_interval};
+use crate::{random_canonical, random_float, random_float_interval};
 
 // some global constants
 pub const INFINITY: f64 = f64::INFINITY;
@@ -262,6 +262,14 @@ impl Vec3<f64> {
         }
     }
 
+    pub fn abs(&self) -> Self {
+        Vec3 {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs(),
+        }
+    }
+
     pub fn origin() -> Self {
         Vec3 {
             x: 0.0,
@@ -319,6 +327,16 @@ impl Vec3<f64> {
 
     }
 
+    // direction above the xy plane, with density proportional to its z (the cosine to +z)
+    // put it in a surface's frame for diffuse bounces
+    pub fn random_cosine_direction() -> Vec3<f64>
+    {
+        let r1 = random_canonical();
+        let r2 = random_canonical();
+        let phi = 2.0 * PI * r1;
+        Vec3::new(phi.cos() * r2.sqrt(), phi.sin() * r2.sqrt(), (1.0 - r2).sqrt())
+    }
+
     // generate random Vec3s
     pub fn random_vec3() -> Vec3<f64>
     {
@@ -330,6 +348,16 @@ impl Vec3<f64> {
         Vec3::new(random_float_interval(min, max), random_float_interval(min, max), random_float_interval(min, max))
     }
 
+    // access a component by axis index, 0 = x, 1 = y, 2 = z
+    pub fn axis(&self, n: usize) -> f64
+    {
+        match n {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
     pub fn near_zero(&self) -> bool{
         let s = 1e-8;
         self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
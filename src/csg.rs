@@ -0,0 +1,190 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::{Interval, IntervalWorldChoice};
+use crate::ray::Ray;
+
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,     // left minus right
+}
+
+impl CsgOp {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// constructive solid geometry node combining two closed solids
+// both children are intersected along the whole ray, then the entering/exiting events are walked
+// in order and only the ones where the combined solid changes between inside and outside are kept
+pub struct Csg {
+    op: CsgOp,
+    left: Rc<dyn Hittable>,
+    right: Rc<dyn Hittable>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self { op, left, right }
+    }
+
+    pub fn union(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Union, left, right)
+    }
+
+    pub fn intersection(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Intersection, left, right)
+    }
+
+    pub fn difference(left: Rc<dyn Hittable>, right: Rc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Difference, left, right)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut hits = Vec::new();
+        self.hit_all(ray, interval, &mut hits);
+
+        match hits.into_iter().next() {
+            Some(first) => {
+                *hit_record = first;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+
+        match self.op {
+            CsgOp::Union => Aabb::surrounding(&left, &right),
+            // only the overlap of the two boxes can be inside both
+            CsgOp::Intersection => {
+                let overlap = |a: &Interval, b: &Interval| Interval::new(a.min().max(b.min()), a.max().min(b.max()));
+                Aabb::new(overlap(&left.x, &right.x), overlap(&left.y, &right.y), overlap(&left.z, &right.z))
+            }
+            // cutting something away never makes the left solid bigger
+            CsgOp::Difference => left,
+        }
+    }
+
+    fn hit_all(&self, ray: &Ray, interval: &Interval, hits: &mut Vec<HitRecord>) {
+        // look along the entire line so we know whether the interval starts inside either solid,
+        // at t = -infinity the ray is outside of both
+        let universe = Interval::world_choice(IntervalWorldChoice::Universe);
+
+        let mut left_hits = Vec::new();
+        let mut right_hits = Vec::new();
+        self.left.hit_all(ray, &universe, &mut left_hits);
+        self.right.hit_all(ray, &universe, &mut right_hits);
+
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut l = 0;
+        let mut r = 0;
+
+        // merge the two sorted lists of events
+        while l < left_hits.len() || r < right_hits.len() {
+            let from_left = r >= right_hits.len() || (l < left_hits.len() && left_hits[l].t <= right_hits[r].t);
+
+            let was_inside = self.op.inside(in_left, in_right);
+            let event = if from_left {
+                l += 1;
+                in_left = left_hits[l - 1].front_face;
+                &left_hits[l - 1]
+            } else {
+                r += 1;
+                in_right = right_hits[r - 1].front_face;
+                &right_hits[r - 1]
+            };
+            let is_inside = self.op.inside(in_left, in_right);
+
+            if was_inside == is_inside || !interval.surrounds(event.t) {
+                continue;
+            }
+
+            let mut boundary = event.clone();
+
+            // the right solid's surface bounds a difference from the other side, so its outward
+            // normal flips; the stored normal already faces the ray, only the facing changes
+            if self.op == CsgOp::Difference && !from_left {
+                boundary.front_face = !boundary.front_face;
+            }
+
+            hits.push(boundary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Vec3;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    // unit spheres around x = 0 and x = 1, overlapping between x = 0 and x = 1
+    fn combine(op: CsgOp) -> Csg {
+        let mat = Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        Csg::new(
+            op,
+            Rc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, mat.clone())),
+            Rc::new(Sphere::new(Vec3::new(1.0, 0.0, 0.0), 1.0, mat)),
+        )
+    }
+
+    // (t, front_face) of every boundary crossing of a ray along +x from x = start
+    fn crossings(csg: &Csg, start: f64) -> Vec<(f64, bool)> {
+        let ray = Ray::new(Vec3::new(start, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = Vec::new();
+        csg.hit_all(&ray, &Interval::new(0.0, f64::INFINITY), &mut hits);
+        hits.iter().map(|hit| ((hit.t * 1e9).round() / 1e9, hit.front_face)).collect()
+    }
+
+    #[test]
+    fn ray_from_outside_both_spheres() {
+        assert_eq!(crossings(&combine(CsgOp::Union), -5.0), [(4.0, true), (7.0, false)]);
+        assert_eq!(crossings(&combine(CsgOp::Intersection), -5.0), [(5.0, true), (6.0, false)]);
+
+        // the bite taken out by the right sphere is left through its front, so that crossing faces outwards
+        assert_eq!(crossings(&combine(CsgOp::Difference), -5.0), [(4.0, true), (5.0, false)]);
+    }
+
+    #[test]
+    fn ray_starting_inside_one_sphere() {
+        // from x = -0.5, inside the left sphere only
+        assert_eq!(crossings(&combine(CsgOp::Union), -0.5), [(2.5, false)]);
+        assert_eq!(crossings(&combine(CsgOp::Intersection), -0.5), [(0.5, true), (1.5, false)]);
+        assert_eq!(crossings(&combine(CsgOp::Difference), -0.5), [(0.5, false)]);
+
+        // from x = 0.5, inside both: the difference is empty along the rest of the ray
+        assert_eq!(crossings(&combine(CsgOp::Intersection), 0.5), [(0.5, false)]);
+        assert_eq!(crossings(&combine(CsgOp::Difference), 0.5), []);
+    }
+
+    #[test]
+    fn disjoint_intersection_is_empty() {
+        let mat = Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let csg = Csg::intersection(
+            Rc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, mat.clone())),
+            Rc::new(Sphere::new(Vec3::new(3.0, 0.0, 0.0), 1.0, mat)),
+        );
+
+        assert!(crossings(&csg, -5.0).is_empty());
+
+        // the boxes do not overlap along x, so neither does anything cross the combined box
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!csg.bounding_box().hit(&ray, &mut Interval::new(0.0, f64::INFINITY)));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::aabb::Aabb;
+use crate::core::{Vec3, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// axis aligned box intersected directly with the slab test
+// the slab that is entered last (or left first) tells which face was hit and so the normal
+// rotate or move it with the RotateY / Translate instances
+pub struct Cuboid {
+    bbox: Aabb,
+    mat: Rc<dyn Material>,
+}
+
+impl Cuboid {
+    // a and b are opposite corners, in any order
+    pub fn new(a: Vec3<f64>, b: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        Self { bbox: Aabb::from_points(&a, &b), mat }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut t_near = -INFINITY;
+        let mut t_far = INFINITY;
+        let mut near_face = (0, 0.0);   // (axis, sign of the outward normal)
+        let mut far_face = (0, 0.0);
+
+        for axis in 0..3 {
+            let slab = self.bbox.axis_interval(axis);
+            let origin = ray.origin().axis(axis);
+            let inv_d = 1.0 / ray.direction().axis(axis);
+
+            let t0 = (slab.min() - origin) * inv_d;
+            let t1 = (slab.max() - origin) * inv_d;
+
+            // travelling along +axis the ray enters through the min face and leaves through the max face
+            let (enter, exit, enter_sign) = if inv_d >= 0.0 { (t0, t1, -1.0) } else { (t1, t0, 1.0) };
+
+            if enter > t_near {
+                t_near = enter;
+                near_face = (axis, enter_sign);
+            }
+            if exit < t_far {
+                t_far = exit;
+                far_face = (axis, -enter_sign);
+            }
+        }
+
+        if t_near > t_far {
+            return false;
+        }
+
+        // from outside we hit the entry face, from inside (refraction) the exit face
+        let (t, (axis, sign)) = if interval.surrounds(t_near) {
+            (t_near, near_face)
+        } else if interval.surrounds(t_far) {
+            (t_far, far_face)
+        } else {
+            return false;
+        };
+
+        // snap the point onto the face plane, only the two in-plane coordinates carry rounding error
+        let slab = self.bbox.axis_interval(axis);
+        let face = if sign < 0.0 { slab.min() } else { slab.max() };
+        let p = ray.at(t);
+
+        hit_record.t = t;
+        hit_record.point = match axis {
+            0 => Vec3::new(face, *p.y(), *p.z()),
+            1 => Vec3::new(*p.x(), face, *p.z()),
+            _ => Vec3::new(*p.x(), *p.y(), face),
+        };
+        hit_record.error = gamma(3) * hit_record.point.abs();
+
+        let outward_normal = match axis {
+            0 => Vec3::new(sign, 0.0, 0.0),
+            1 => Vec3::new(0.0, sign, 0.0),
+            _ => Vec3::new(0.0, 0.0, sign),
+        };
+        hit_record.set_face_normal(ray, &outward_normal);
+
+        // uv across the face from the two other axes
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let along = |n: usize| {
+            let slab = self.bbox.axis_interval(n);
+            (hit_record.point.axis(n) - slab.min()) / slab.size()
+        };
+        hit_record.u = along(a);
+        hit_record.v = along(b);
+        let edge = |n: usize| match n {
+            0 => Vec3::new(self.bbox.x.size(), 0.0, 0.0),
+            1 => Vec3::new(0.0, self.bbox.y.size(), 0.0),
+            _ => Vec3::new(0.0, 0.0, self.bbox.z.size()),
+        };
+        hit_record.tangent = edge(a);
+        hit_record.bitangent = edge(b);
+
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.pad_to_minimums()
+    }
+}
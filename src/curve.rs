@@ -0,0 +1,405 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::Material;
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::rc::Rc;
+
+// how the swept width of a curve is turned into a surface
+#[derive(Clone, Copy)]
+pub enum CurveMode {
+    Flat,               // a ribbon that always faces the incoming ray, cheap hair and fur
+    Ribbon(Vec3<f64>),  // a ribbon whose face points along the given normal, grass blades
+    Round,              // shaded as a cylinder around the curve, hair seen up close
+}
+
+// one cubic bezier segment with a width that changes linearly along it
+// intersected like pbrt: the control points are moved into a frame where the ray runs down +z from the origin,
+// then the curve is split recursively until the pieces are nearly straight and each piece is tested as a line
+// the hit record gets u along the curve (over the whole strand), v across it and the tangent
+pub struct Curve {
+    cp: [Vec3<f64>; 4],
+    width: [f64; 2],
+    u_range: [f64; 2],  // where this segment sits along its strand
+    mode: CurveMode,
+    max_depth: u32,
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
+}
+
+// a hit found during the recursive search, u is local to the segment
+struct CurveHit {
+    t: f64,             // ray space z, the distance along the normalized ray
+    u: f64,
+    v: f64,
+    hit_width: f64,
+}
+
+// what the recursive search needs to know about the ray, in ray space
+struct RaySpace {
+    dir: Vec3<f64>,     // the normalized world space direction, for ribbon orientation
+    z_range: Interval,  // the hit interval converted to distances along the ray
+}
+
+impl Curve {
+    pub fn new(cp: [Vec3<f64>; 4], width0: f64, width1: f64, mode: CurveMode, mat: Rc<dyn Material>) -> Self {
+        Self::segment(cp, [width0, width1], [0.0, 1.0], mode, mat)
+    }
+
+    fn segment(cp: [Vec3<f64>; 4], width: [f64; 2], u_range: [f64; 2], mode: CurveMode, mat: Rc<dyn Material>) -> Self {
+        let half_width = width[0].max(width[1]) / 2.0;
+        let mut bbox = Aabb::empty();
+        for p in cp.iter() {
+            let pad = Vec3::new(half_width, half_width, half_width);
+            bbox = Aabb::surrounding(&bbox, &Aabb::from_points(&(*p - pad), &(*p + pad)));
+        }
+
+        // split until the segments are flat to within a fraction of the width (pbrt's bound)
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let second_difference = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0.max(second_difference.x().abs()).max(second_difference.y().abs()).max(second_difference.z().abs());
+        }
+        let eps = width[0].max(width[1]) * 0.05;
+        let r0 = (std::f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0;
+        let max_depth = if r0.is_finite() { r0.round().clamp(0.0, 10.0) as u32 } else { 0 };
+
+        Self { cp, width, u_range, mode, max_depth, mat, bbox }
+    }
+
+    // a strand through 3n + 1 bezier control points (shared end points), one segment per 3 points
+    pub fn bezier_strand(points: &[Vec3<f64>], root_width: f64, tip_width: f64, mode: CurveMode, mat: Rc<dyn Material>) -> Vec<Curve> {
+        let count = points.len().saturating_sub(1) / 3;
+        (0..count)
+            .map(|i| [points[3 * i], points[3 * i + 1], points[3 * i + 2], points[3 * i + 3]])
+            .enumerate()
+            .map(|(i, cp)| Self::strand_segment(cp, i, count, root_width, tip_width, mode, &mat))
+            .collect()
+    }
+
+    // a strand through uniform cubic b-spline control points, n points give n - 3 segments
+    // each segment is converted to its equivalent bezier form
+    pub fn bspline_strand(points: &[Vec3<f64>], root_width: f64, tip_width: f64, mode: CurveMode, mat: Rc<dyn Material>) -> Vec<Curve> {
+        let count = points.len().saturating_sub(3);
+        (0..count)
+            .map(|i| {
+                let (p0, p1, p2, p3) = (points[i], points[i + 1], points[i + 2], points[i + 3]);
+                [
+                    (p0 + 4.0 * p1 + p2) / 6.0,
+                    (2.0 * p1 + p2) / 3.0,
+                    (p1 + 2.0 * p2) / 3.0,
+                    (p1 + 4.0 * p2 + p3) / 6.0,
+                ]
+            })
+            .enumerate()
+            .map(|(i, cp)| Self::strand_segment(cp, i, count, root_width, tip_width, mode, &mat))
+            .collect()
+    }
+
+    fn strand_segment(cp: [Vec3<f64>; 4], i: usize, count: usize, root_width: f64, tip_width: f64, mode: CurveMode, mat: &Rc<dyn Material>) -> Curve {
+        let u0 = i as f64 / count as f64;
+        let u1 = (i + 1) as f64 / count as f64;
+        let width_at = |u: f64| root_width + (tip_width - root_width) * u;
+        Self::segment(cp, [width_at(u0), width_at(u1)], [u0, u1], mode, Rc::clone(mat))
+    }
+
+    fn width_at(&self, u: f64) -> f64 {
+        self.width[0] + (self.width[1] - self.width[0]) * u
+    }
+
+    // recursive search over the sub-segment cp (in ray space) covering [u0, u1] of this segment
+    fn recurse(&self, cp: &[Vec3<f64>; 4], (u0, u1): (f64, f64), depth: u32, ray_space: &RaySpace, best: &mut Option<CurveHit>) {
+        let z_range = &ray_space.z_range;
+
+        // anything past the closest hit so far can be skipped
+        let z_max = best.as_ref().map_or(z_range.max(), |hit| hit.t);
+        let half_width = self.width_at(u0).max(self.width_at(u1)) / 2.0;
+
+        // does the ray (the +z axis) pass through the padded box around these control points?
+        let (mut lo, mut hi) = (cp[0], cp[0]);
+        for p in cp.iter().skip(1) {
+            lo = Vec3::new(lo.x().min(*p.x()), lo.y().min(*p.y()), lo.z().min(*p.z()));
+            hi = Vec3::new(hi.x().max(*p.x()), hi.y().max(*p.y()), hi.z().max(*p.z()));
+        }
+        if lo.x() - half_width > 0.0 || hi.x() + half_width < 0.0 || lo.y() - half_width > 0.0 || hi.y() + half_width < 0.0 {
+            return;
+        }
+        if lo.z() - half_width > z_max || hi.z() + half_width < z_range.min() {
+            return;
+        }
+
+        if depth > 0 {
+            let (left, right) = split_bezier(cp);
+            let u_mid = 0.5 * (u0 + u1);
+            self.recurse(&left, (u0, u_mid), depth - 1, ray_space, best);
+            self.recurse(&right, (u_mid, u1), depth - 1, ray_space, best);
+            return;
+        }
+
+        // the ray must lie between the perpendiculars to the curve at both ends of the piece
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return;
+        }
+
+        // treat the piece as a line and find the point closest to the ray
+        let (sx, sy) = (cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y());
+        let denom = sx * sx + sy * sy;
+        if denom == 0.0 {
+            return;
+        }
+        let w = (-cp[0].x() * sx - cp[0].y() * sy) / denom;
+        let u = (u0 + (u1 - u0) * w).clamp(u0, u1);
+
+        let mut hit_width = self.width_at(u);
+        if let CurveMode::Ribbon(normal) = self.mode {
+            // a ribbon seen edge on gets thinner
+            hit_width *= normal.normalize().dot(&ray_space.dir).abs();
+        }
+
+        let (pc, dpcdw) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let dist2 = pc.x() * pc.x() + pc.y() * pc.y();
+        if dist2 > hit_width * hit_width * 0.25 || !z_range.surrounds(*pc.z()) || *pc.z() > z_max {
+            return;
+        }
+
+        // v runs across the width, 0.5 on the centre line
+        let dist = dist2.sqrt();
+        let edge_func = dpcdw.x() * -pc.y() + pc.x() * dpcdw.y();
+        let v = if edge_func > 0.0 { 0.5 + dist / hit_width } else { 0.5 - dist / hit_width };
+
+        *best = Some(CurveHit { t: *pc.z(), u, v, hit_width });
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let ray_length = ray.direction().mag();
+        if ray_length == 0.0 {
+            return false;
+        }
+        let ray_dir = ray.direction() / ray_length;
+
+        // frame where the ray starts at the origin and travels along +z, distances along z are t * |d|
+        let frame = Onb::new(&ray_dir);
+        let cp = self.cp.map(|p| frame.to_local(&(p - *ray.origin())));
+        let ray_space = RaySpace {
+            dir: ray_dir,
+            z_range: Interval::new(interval.min() * ray_length, interval.max() * ray_length),
+        };
+
+        let mut best = None;
+        self.recurse(&cp, (0.0, 1.0), self.max_depth, &ray_space, &mut best);
+
+        let Some(found) = best else {
+            return false;
+        };
+
+        let t = found.t / ray_length;
+        hit_record.t = t;
+        hit_record.point = ray.at(t);
+
+        // the hit is only found to within the flattened segment, pbrt bounds it by twice the width there
+        let width = 2.0 * found.hit_width;
+        hit_record.error = Vec3::new(width, width, width);
+
+        let (centre, derivative) = eval_bezier(&self.cp, found.u);
+        let tangent = derivative.normalize();
+
+        // the side of the ribbon facing the ray
+        let facing = -(ray_dir - tangent * ray_dir.dot(&tangent)).normalize();
+
+        let outward_normal = match self.mode {
+            CurveMode::Flat => facing,
+            CurveMode::Ribbon(normal) => (normal - tangent * normal.dot(&tangent)).normalize(),
+            CurveMode::Round => {
+                // bend the normal around the curve by how far off centre the ray passed
+                let side = tangent.cross(&ray_dir).normalize();
+                let offset = ((hit_record.point - centre).dot(&side) / (found.hit_width / 2.0)).clamp(-1.0, 1.0);
+                (1.0 - offset * offset).sqrt() * facing + offset * side
+            }
+        };
+        hit_record.set_face_normal(ray, &outward_normal);
+
+        hit_record.u = self.u_range[0] + (self.u_range[1] - self.u_range[0]) * found.u;
+        hit_record.v = found.v;
+
+        // u runs over the whole strand, this segment only covers u_range of it; v runs across the width as in pbrt
+        hit_record.tangent = derivative / (self.u_range[1] - self.u_range[0]);
+        hit_record.bitangent = found.hit_width * hit_record.normal.cross(&tangent).normalize();
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// point and derivative of a cubic bezier at u (de casteljau)
+fn eval_bezier(cp: &[Vec3<f64>; 4], u: f64) -> (Vec3<f64>, Vec3<f64>) {
+    let lerp = |a: &Vec3<f64>, b: &Vec3<f64>| *a + (*b - *a) * u;
+    let cp1 = [lerp(&cp[0], &cp[1]), lerp(&cp[1], &cp[2]), lerp(&cp[2], &cp[3])];
+    let cp2 = [lerp(&cp1[0], &cp1[1]), lerp(&cp1[1], &cp1[2])];
+
+    // the derivative vanishes at an end with doubled control points, fall back to the chord
+    let mut derivative = 3.0 * (cp2[1] - cp2[0]);
+    if derivative.near_zero() {
+        derivative = cp[3] - cp[0];
+    }
+
+    (lerp(&cp2[0], &cp2[1]), derivative)
+}
+
+// split a cubic bezier in half
+fn split_bezier(cp: &[Vec3<f64>; 4]) -> ([Vec3<f64>; 4], [Vec3<f64>; 4]) {
+    let mid = |a: &Vec3<f64>, b: &Vec3<f64>| (*a + *b) * 0.5;
+    let p01 = mid(&cp[0], &cp[1]);
+    let p12 = mid(&cp[1], &cp[2]);
+    let p23 = mid(&cp[2], &cp[3]);
+    let p012 = mid(&p01, &p12);
+    let p123 = mid(&p12, &p23);
+    let p0123 = mid(&p012, &p123);
+
+    ([cp[0], p01, p012, p0123], [p0123, p123, p23, cp[3]])
+}
+
+// many curve segments (a head of hair, a patch of grass) kept in a bvh so a ray only tests the few it passes near
+// the hit record's index is the segment that was hit, in the order they were given
+pub struct Strands {
+    segments: Vec<Curve>,
+    bvh: Bvh,
+}
+
+impl Strands {
+    pub fn new(segments: Vec<Curve>) -> Self {
+        let boxes: Vec<Aabb> = segments.iter().map(|s| s.bbox).collect();
+        Self { bvh: Bvh::new(&boxes), segments }
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+impl Hittable for Strands {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        // a segment only writes the record when it finds a hit inside the shrinking interval, so the last write is the closest
+        let mut closest = None;
+        self.bvh.hit(ray, interval, |i, ray_t| {
+            if !self.segments[i].hit(ray, ray_t, hit_record) {
+                return None;
+            }
+            closest = Some(i);
+            Some(hit_record.t)
+        });
+
+        let Some(i) = closest else {
+            return false;
+        };
+        hit_record.index = i;
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+// loads a text file of strands, one per line:
+//     <bezier|bspline> <root width> <tip width> x0 y0 z0 x1 y1 z1 ...
+// blank lines and lines starting with # are skipped
+pub fn load_strands(path: impl AsRef<Path>, mode: CurveMode, mat: Rc<dyn Material>) -> std::io::Result<Strands> {
+    let text = fs::read_to_string(path)?;
+    let mut segments = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("strand file line {}: {}", line_number + 1, msg));
+
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().unwrap_or_default();
+        let numbers: Vec<f64> = fields
+            .map(|f| f.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("bad number"))?;
+
+        if numbers.len() < 2 || !(numbers.len() - 2).is_multiple_of(3) {
+            return Err(invalid("expected two widths followed by x y z triples"));
+        }
+        let (root_width, tip_width) = (numbers[0], numbers[1]);
+        let points: Vec<Vec3<f64>> = numbers[2..].chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect();
+
+        match kind {
+            "bezier" if points.len() >= 4 && (points.len() - 1).is_multiple_of(3) => segments.extend(Curve::bezier_strand(&points, root_width, tip_width, mode, Rc::clone(&mat))),
+            "bezier" => return Err(invalid("a bezier strand needs 3n + 1 control points")),
+            "bspline" if points.len() >= 4 => segments.extend(Curve::bspline_strand(&points, root_width, tip_width, mode, Rc::clone(&mat))),
+            "bspline" => return Err(invalid("a b-spline strand needs at least 4 control points")),
+            _ => return Err(invalid("strand type must be bezier or bspline")),
+        }
+    }
+
+    Ok(Strands::new(segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DefaultMaterial;
+    use crate::seeded_rng;
+    use rand::Rng;
+
+    #[test]
+    fn strands_find_the_same_hits_as_testing_every_segment() {
+        let mut rng = seeded_rng(7);
+        let mat: Rc<dyn Material> = Rc::new(DefaultMaterial {});
+        let mut segments = Vec::new();
+        for strand in 0..20 {
+            let x = strand as f64 * 0.1 - 1.0;
+            let points = [Vec3::new(x, -1.0, 0.0), Vec3::new(x + 0.2, -0.3, 0.1), Vec3::new(x - 0.1, 0.3, -0.1), Vec3::new(x, 1.0, 0.0)];
+            segments.extend(Curve::bezier_strand(&points, 0.05, 0.01, CurveMode::Flat, Rc::clone(&mat)));
+        }
+        let strands = Strands::new(segments);
+        assert_eq!(strands.len(), 20);
+
+        let mut hits = 0;
+        for _ in 0..200 {
+            let target = Vec3::new(rng.random_range(-1.2..1.2), rng.random_range(-1.0..1.0), 0.0);
+            let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), target - Vec3::new(0.0, 0.0, 5.0));
+            let interval = Interval::new(0.0, f64::INFINITY);
+
+            let mut record = HitRecord::default();
+            let found = strands.hit(&ray, &interval, &mut record).then_some((record.t, record.index));
+
+            let mut brute = None;
+            for (i, segment) in strands.segments.iter().enumerate() {
+                let mut r = HitRecord::default();
+                if segment.hit(&ray, &interval, &mut r) && brute.is_none_or(|(t, _)| r.t < t) {
+                    brute = Some((r.t, i));
+                }
+            }
+            assert_eq!(found, brute);
+            hits += found.is_some() as u32;
+        }
+        assert!(hits > 0);
+    }
+}
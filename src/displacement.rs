@@ -0,0 +1,237 @@
+use crate::camera::Camera;
+use crate::core::Vec3;
+use crate::degrees_to_radians;
+use crate::mesh::Mesh;
+
+use std::collections::HashMap;
+
+// how finely a mesh is cut up before it is displaced
+#[derive(Debug, Clone, Copy)]
+pub enum EdgeTarget {
+    World(f64),                                                     // longest edge in world units
+    Screen { eye: Vec3<f64>, pixels_per_radian: f64, pixels: f64 }, // longest edge as seen from eye, in pixels
+}
+
+impl EdgeTarget {
+    // edges no longer than the given number of pixels in the camera's picture
+    pub fn screen(camera: &Camera, pixels: f64) -> Self {
+        let image_height = (camera.image_width as f64 / camera.aspect_ratio).max(1.0);
+        let pixels_per_radian = image_height / (2.0 * (degrees_to_radians(camera.vfov) / 2.0).tan());
+        EdgeTarget::Screen { eye: camera.lookfrom, pixels_per_radian, pixels }
+    }
+
+    fn too_long(&self, a: &Vec3<f64>, b: &Vec3<f64>) -> bool {
+        let length = (b - a).mag();
+        match *self {
+            EdgeTarget::World(max) => length > max,
+            EdgeTarget::Screen { eye, pixels_per_radian, pixels } => {
+                let distance = (0.5 * (*a + *b) - eye).mag().max(1e-9);
+                length / distance * pixels_per_radian > pixels
+            }
+        }
+    }
+}
+
+// most passes the tessellator makes, each one at least halves the edges it touches
+const MAX_PASSES: usize = 16;
+
+// cuts the mesh into triangles no longer than the target and moves every vertex along its normal by
+// height(point, u, v); the result carries normals of the displaced surface and goes into TriangleMesh
+// like any other mesh, whose bvh is built from the displaced positions
+// splitting is decided per edge so neighbouring triangles always agree and no cracks open up;
+// normals and uvs are taken per vertex (the first face corner wins at a uv seam) for the same reason
+// creases are dropped since their edges no longer exist
+pub fn displace(mesh: &Mesh, target: EdgeTarget, height: impl Fn(&Vec3<f64>, f64, f64) -> f64) -> Mesh {
+    let mesh = mesh.triangulate();
+
+    let mut positions = mesh.positions.clone();
+    let mut normals = vertex_normals(&mesh);
+    let mut uvs: Vec<[f64; 2]> = vec![[0.0, 0.0]; positions.len()];
+    let mut corner_uvs: Option<Vec<[[f64; 2]; 3]>> = mesh.uvs.as_ref().map(|uvs| uvs.iter().map(|c| [c[0], c[1], c[2]]).collect());
+    let mut triangles: Vec<[usize; 3]> = mesh.faces.iter().map(|f| [f[0], f[1], f[2]]).collect();
+
+    if let Some(corners) = &corner_uvs {
+        for (tri, uv) in triangles.iter().zip(corners.iter()).rev() {
+            for k in 0..3 {
+                uvs[tri[k]] = uv[k];
+            }
+        }
+    }
+
+    for _ in 0..MAX_PASSES {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut split_triangles = Vec::with_capacity(triangles.len());
+        let mut split_uvs = Vec::with_capacity(triangles.len());
+
+        for (t, tri) in triangles.iter().enumerate() {
+            let corner_uv = corner_uvs.as_ref().map(|c| c[t]);
+
+            // index of the new vertex halfway along each edge that is too long
+            let mut split = [None; 3];
+            for k in 0..3 {
+                let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                if !target.too_long(&positions[a], &positions[b]) {
+                    continue;
+                }
+                let key = (a.min(b), a.max(b));
+                let m = *midpoints.entry(key).or_insert_with(|| {
+                    positions.push(0.5 * (positions[a] + positions[b]));
+                    normals.push((normals[a] + normals[b]).normalize());
+                    uvs.push(mid_uv(uvs[a], uvs[b]));
+                    positions.len() - 1
+                });
+                split[k] = Some(m);
+            }
+
+            for (corners, local) in split_pattern(tri, &split) {
+                split_triangles.push(corners);
+                if let Some(uv) = corner_uv {
+                    split_uvs.push(local.map(|w| interpolate_uv(&uv, w)));
+                }
+            }
+        }
+
+        let done = split_triangles.len() == triangles.len();
+        triangles = split_triangles;
+        if corner_uvs.is_some() {
+            corner_uvs = Some(split_uvs);
+        }
+        if done {
+            break;
+        }
+    }
+
+    for (v, position) in positions.iter_mut().enumerate() {
+        let offset = height(position, uvs[v][0], uvs[v][1]);
+        *position += offset * normals[v];
+    }
+
+    let mut displaced = Mesh::new(positions, triangles.iter().map(|t| t.to_vec()).collect());
+    displaced.uvs = corner_uvs.map(|uvs| uvs.iter().map(|c| c.to_vec()).collect());
+    let normals = vertex_normals(&displaced);
+    displaced.normals = Some(triangles.iter().map(|t| t.iter().map(|&v| normals[v]).collect()).collect());
+    displaced
+}
+
+// one normal per position: the mesh's own corner normals averaged, or the area weighted face normals
+fn vertex_normals(mesh: &Mesh) -> Vec<Vec3<f64>> {
+    let mut sums = vec![Vec3::origin(); mesh.positions.len()];
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let [a, b, c] = [face[0], face[1], face[2]].map(|i| mesh.positions[i]);
+        let face_normal = (b - a).cross(&(c - a));
+        for (k, &v) in face.iter().enumerate() {
+            sums[v] += match &mesh.normals {
+                Some(normals) => normals[f][k],
+                None => face_normal,
+            };
+        }
+    }
+    sums.iter().map(|n| if n.mag() > 0.0 { n.normalize() } else { Vec3::new(0.0, 1.0, 0.0) }).collect()
+}
+
+fn mid_uv(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])]
+}
+
+fn interpolate_uv(uv: &[[f64; 2]; 3], w: [f64; 3]) -> [f64; 2] {
+    [
+        w[0] * uv[0][0] + w[1] * uv[1][0] + w[2] * uv[2][0],
+        w[0] * uv[0][1] + w[1] * uv[1][1] + w[2] * uv[2][1],
+    ]
+}
+
+// child triangles of tri given the midpoints of its split edges (edge k runs from corner k to k + 1)
+// each child comes with the barycentric weights of its corners in the parent, to carry uvs along
+fn split_pattern(tri: &[usize; 3], split: &[Option<usize>; 3]) -> Vec<([usize; 3], [[f64; 3]; 3])> {
+    const CORNERS: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    const MIDPOINTS: [[f64; 3]; 3] = [[0.5, 0.5, 0.0], [0.0, 0.5, 0.5], [0.5, 0.0, 0.5]];
+
+    let count = split.iter().filter(|m| m.is_some()).count();
+    if count == 0 {
+        return vec![(*tri, CORNERS)];
+    }
+
+    if let [Some(m0), Some(m1), Some(m2)] = *split {
+        return vec![
+            ([tri[0], m0, m2], [CORNERS[0], MIDPOINTS[0], MIDPOINTS[2]]),
+            ([m0, tri[1], m1], [MIDPOINTS[0], CORNERS[1], MIDPOINTS[1]]),
+            ([m2, m1, tri[2]], [MIDPOINTS[2], MIDPOINTS[1], CORNERS[2]]),
+            ([m0, m1, m2], [MIDPOINTS[0], MIDPOINTS[1], MIDPOINTS[2]]),
+        ];
+    }
+
+    // rotate so the split edge (or the first of two) starts at corner r
+    let r = if count == 1 {
+        (0..3).find(|&k| split[k].is_some()).unwrap_or(0)
+    } else {
+        (0..3).find(|&k| split[k].is_some() && split[(k + 1) % 3].is_some()).unwrap_or(0)
+    };
+    let (a, b, c) = (r, (r + 1) % 3, (r + 2) % 3);
+    let m_ab = split[a].unwrap_or(tri[a]);
+
+    if count == 1 {
+        return vec![
+            ([tri[a], m_ab, tri[c]], [CORNERS[a], MIDPOINTS[a], CORNERS[c]]),
+            ([m_ab, tri[b], tri[c]], [MIDPOINTS[a], CORNERS[b], CORNERS[c]]),
+        ];
+    }
+
+    // edges ab and bc are split: cut off the corner at b, then halve the quad that is left
+    let m_bc = split[b].unwrap_or(tri[b]);
+    vec![
+        ([m_ab, tri[b], m_bc], [MIDPOINTS[a], CORNERS[b], MIDPOINTS[b]]),
+        ([tri[a], m_ab, m_bc], [CORNERS[a], MIDPOINTS[a], MIDPOINTS[b]]),
+        ([tri[a], m_bc, tri[c]], [CORNERS[a], MIDPOINTS[b], CORNERS[c]]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::material::Lambertian;
+    use crate::mesh::TriangleMesh;
+
+    use std::rc::Rc;
+
+    // the [-1, 1] cube, quads wound outwards
+    fn cube() -> Mesh {
+        let positions = (0..8)
+            .map(|i| Vec3::new(if i & 1 == 0 { -1.0 } else { 1.0 }, if i & 2 == 0 { -1.0 } else { 1.0 }, if i & 4 == 0 { -1.0 } else { 1.0 }))
+            .collect();
+        let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        Mesh::new(positions, faces.iter().map(|f| f.to_vec()).collect())
+    }
+
+    #[test]
+    fn uneven_tessellation_leaves_no_cracks() {
+        // seen from just in front of the +z face, which gets cut far finer than the -z face
+        let target = EdgeTarget::Screen { eye: Vec3::new(0.0, 0.0, 1.5), pixels_per_radian: 100.0, pixels: 20.0 };
+        let displaced = displace(&cube(), target, |p, _, _| 0.1 * (3.0 * p.x()).sin() * (2.0 * p.y()).cos());
+
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut shortest = f64::INFINITY;
+        let mut longest: f64 = 0.0;
+        for face in displaced.faces.iter() {
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+
+                let length = (displaced.positions[b] - displaced.positions[a]).mag();
+                shortest = shortest.min(length);
+                longest = longest.max(length);
+            }
+        }
+
+        // a vertex in the middle of a neighbour's edge (a t-junction) would leave that edge with one face
+        assert!(edges.values().all(|&count| count == 2), "the displaced cube is not closed");
+        assert!(longest > 4.0 * shortest, "faces were not cut to different sizes ({} to {})", shortest, longest);
+
+        let bbox = TriangleMesh::new(&displaced, Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))).bounding_box();
+        for p in displaced.positions.iter() {
+            for axis in 0..3 {
+                assert!(bbox.axis_interval(axis).contains(p.axis(axis)), "{:?} lies outside the bounding box", p);
+            }
+        }
+    }
+}
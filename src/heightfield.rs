@@ -0,0 +1,225 @@
+use crate::aabb::Aabb;
+use crate::core::{Vec3, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::image::Image;
+use crate::interval::Interval;
+use crate::mesh::{intersect_triangle, triangle_derivatives};
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// terrain from a regular grid of elevations
+// the grid spans size.x by size.z starting at corner, sample values (usually in [0, 1]) are scaled by size.y
+// each grid cell is two triangles; rays walk the cells with a 2d dda and skip cells whose
+// min/max height range the ray passes over or under
+pub struct Heightfield {
+    nx: usize,                      // samples along x
+    nz: usize,                      // samples along z
+    heights: Vec<f64>,              // world space height above corner.y, x varies fastest
+    normals: Vec<Vec3<f64>>,        // smooth per sample normals
+    cell_ranges: Vec<(f64, f64)>,   // min and max height inside each cell
+    corner: Vec3<f64>,
+    size: Vec3<f64>,
+    cell_x: f64,
+    cell_z: f64,
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Heightfield {
+    pub fn new(heights: Vec<f64>, nx: usize, nz: usize, corner: Vec3<f64>, size: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        assert!(nx >= 2 && nz >= 2, "a heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * nz, "height data does not match the grid size");
+
+        let heights: Vec<f64> = heights.iter().map(|h| h * size.y()).collect();
+        let cell_x = size.x() / (nx - 1) as f64;
+        let cell_z = size.z() / (nz - 1) as f64;
+
+        // normals from central differences of the height, one sided at the borders
+        let height_at = |i: usize, j: usize| heights[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dhdx = (height_at(i1, j) - height_at(i0, j)) / ((i1 - i0) as f64 * cell_x);
+                let dhdz = (height_at(i, j1) - height_at(i, j0)) / ((j1 - j0) as f64 * cell_z);
+                normals.push(Vec3::new(-dhdx, 1.0, -dhdz).normalize());
+            }
+        }
+
+        let mut cell_ranges = Vec::with_capacity((nx - 1) * (nz - 1));
+        let mut low = INFINITY;
+        let mut high = -INFINITY;
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners = [height_at(i, j), height_at(i + 1, j), height_at(i, j + 1), height_at(i + 1, j + 1)];
+                let min = corners.iter().cloned().fold(INFINITY, f64::min);
+                let max = corners.iter().cloned().fold(-INFINITY, f64::max);
+                low = low.min(min);
+                high = high.max(max);
+                cell_ranges.push((min, max));
+            }
+        }
+
+        let bbox = Aabb::from_points(
+            &Vec3::new(*corner.x(), corner.y() + low, *corner.z()),
+            &Vec3::new(corner.x() + size.x(), corner.y() + high, corner.z() + size.z()),
+        )
+        .pad_to_minimums();
+
+        Self { nx, nz, heights, normals, cell_ranges, corner, size, cell_x, cell_z, mat, bbox }
+    }
+
+    // one sample per pixel, brightness is the height
+    // the top row of the image lands on the -z edge, so seen from above the terrain reads like the picture
+    pub fn from_image(image: &Image, corner: Vec3<f64>, size: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        let (nx, nz) = (image.width(), image.height());
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                heights.push(image.gray(i, j));
+            }
+        }
+        Self::new(heights, nx, nz, corner, size, mat)
+    }
+
+    // procedural terrain, f gets (u, v) in [0, 1]^2 across the grid
+    pub fn from_fn(nx: usize, nz: usize, f: impl Fn(f64, f64) -> f64, corner: Vec3<f64>, size: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                heights.push(f(i as f64 / (nx - 1) as f64, j as f64 / (nz - 1) as f64));
+            }
+        }
+        Self::new(heights, nx, nz, corner, size, mat)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3<f64> {
+        Vec3::new(
+            self.corner.x() + i as f64 * self.cell_x,
+            self.corner.y() + self.heights[j * self.nx + i],
+            self.corner.z() + j as f64 * self.cell_z,
+        )
+    }
+
+    // test the two triangles of cell (i, j), fills the record on the closest hit
+    fn hit_cell(&self, i: usize, j: usize, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest = interval.max();
+        let mut found = None;
+
+        for tri in [[0, 1, 2], [0, 2, 3]] {
+            let [a, b, c] = tri.map(|k| corners[k]);
+            let (p0, p1, p2) = (self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1));
+
+            if let Some((t, b1, b2)) = intersect_triangle(ray, &p0, &p1, &p2)
+                && t > interval.min()
+                && t < closest
+            {
+                closest = t;
+                found = Some(([a, b, c], [p0, p1, p2], b1, b2));
+            }
+        }
+
+        let Some((vertices, points, b1, b2)) = found else {
+            return false;
+        };
+
+        let (w0, w1, w2) = ((1.0 - b1 - b2) * points[0], b1 * points[1], b2 * points[2]);
+        hit_record.t = closest;
+        hit_record.point = w0 + w1 + w2;
+        hit_record.error = gamma(7) * (w0.abs() + w1.abs() + w2.abs());
+
+        // facing comes from the flat triangle, shading uses the interpolated normal
+        let geometric_normal = (points[1] - points[0]).cross(&(points[2] - points[0])).normalize();
+        let [na, nb, nc] = vertices.map(|(vi, vj)| self.normals[vj * self.nx + vi]);
+        let smooth_normal = ((1.0 - b1 - b2) * na + b1 * nb + b2 * nc).normalize();
+
+        hit_record.set_face_normal(ray, &geometric_normal);
+        hit_record.normal = if hit_record.front_face { smooth_normal } else { -smooth_normal };
+
+        let uv_of = |p: &Vec3<f64>| [(p.x() - self.corner.x()) / self.size.x(), (p.z() - self.corner.z()) / self.size.z()];
+        [hit_record.u, hit_record.v] = uv_of(&hit_record.point);
+        (hit_record.tangent, hit_record.bitangent) = triangle_derivatives(&points, &points.map(|p| uv_of(&p)));
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut ray_t = *interval;
+        if !self.bbox.hit(ray, &mut ray_t) {
+            return false;
+        }
+
+        let cells_x = self.nx - 1;
+        let cells_z = self.nz - 1;
+
+        // position and direction in cell units on the xz plane
+        let start = ray.at(ray_t.min());
+        let gx = (start.x() - self.corner.x()) / self.cell_x;
+        let gz = (start.z() - self.corner.z()) / self.cell_z;
+        let dx = ray.direction().x() / self.cell_x;
+        let dz = ray.direction().z() / self.cell_z;
+
+        let mut i = (gx.floor().max(0.0) as usize).min(cells_x - 1);
+        let mut j = (gz.floor().max(0.0) as usize).min(cells_z - 1);
+
+        let setup = |g: f64, d: f64, cell: usize| -> (f64, f64, i64) {
+            if d > 0.0 {
+                (ray_t.min() + ((cell + 1) as f64 - g) / d, 1.0 / d, 1)
+            } else if d < 0.0 {
+                (ray_t.min() + (cell as f64 - g) / d, -1.0 / d, -1)
+            } else {
+                (INFINITY, INFINITY, 0)
+            }
+        };
+        let (mut next_tx, delta_tx, step_x) = setup(gx, dx, i);
+        let (mut next_tz, delta_tz, step_z) = setup(gz, dz, j);
+
+        let oy = *ray.origin().y() - self.corner.y();
+        let dy = *ray.direction().y();
+        let mut t = ray_t.min();
+
+        loop {
+            let t_exit = next_tx.min(next_tz).min(ray_t.max());
+
+            // only look at the triangles if the ray's height over this cell overlaps the terrain's
+            let (low, high) = self.cell_ranges[j * cells_x + i];
+            let (y0, y1) = (oy + t * dy, oy + t_exit * dy);
+            if y0.min(y1) <= high && y0.max(y1) >= low && self.hit_cell(i, j, ray, interval, hit_record) {
+                return true;
+            }
+
+            if t_exit >= ray_t.max() {
+                return false;
+            }
+
+            // step into the neighbouring cell
+            if next_tx < next_tz {
+                let next = i as i64 + step_x;
+                if next < 0 || next >= cells_x as i64 {
+                    return false;
+                }
+                i = next as usize;
+                t = next_tx;
+                next_tx += delta_tx;
+            } else {
+                let next = j as i64 + step_z;
+                if next < 0 || next >= cells_z as i64 {
+                    return false;
+                }
+                j = next as usize;
+                t = next_tz;
+                next_tz += delta_tz;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
@@ -1,5 +1,6 @@
 use std::rc::Rc;
 
+use crate::aabb::Aabb;
 use crate::material::DefaultMaterial;
 use crate::ray::Ray;
 use crate::core::Vec3;
@@ -9,14 +10,58 @@ use crate::Material;
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool;
+
+    // box enclosing the object over the whole shutter interval
+    fn bounding_box(&self) -> Aabb;
+
+    // every intersection inside the interval, nearest first; front_face tells entering from exiting
+    // the default keeps asking for the closest hit past the previous one, objects can override it
+    fn hit_all(&self, ray: &Ray, interval: &Interval, hits: &mut Vec<HitRecord>)
+    {
+        let mut t_min = interval.min();
+        let mut hit_record = HitRecord::default();
+
+        // hit() only accepts roots strictly inside the interval, but a tangent ray or a root found
+        // iteratively can come back a hair past the previous one forever, so step clear of each root
+        // and give up after MAX_HITS crossings
+        for _ in 0..MAX_HITS
+        {
+            hit_record.clear_surface();
+            if !self.hit(ray, &Interval::new(t_min, interval.max()), &mut hit_record)
+            {
+                break;
+            }
+            t_min = hit_record.t + ROOT_SEPARATION * hit_record.t.abs().max(1.0);
+            hits.push(hit_record.clone());
+        }
+    }
 }
 
+// most crossings the default hit_all collects along one ray
+const MAX_HITS: usize = 64;
+
+// roots closer together than this (relative to t) count as the same crossing in hit_all
+const ROOT_SEPARATION: f64 = 1e-9;
+
+// grazing hits stretch the footprint by at most 1 / MIN_INCIDENCE
+const MIN_INCIDENCE: f64 = 1.0 / 64.0;
+
 #[derive(Clone)]
 pub struct HitRecord {
     pub t: f64,
     pub point: Vec3<f64>,
     pub mat: Rc<dyn Material>,
-    pub normal: Vec3<f64>,
+    pub normal: Vec3<f64>,  // shading normal, against the ray; meshes and heightfields smooth it
+    pub geometric_normal: Vec3<f64>,    // normal of the surface itself, on the same side as normal
+    pub u: f64,             // surface coordinates of the hit point, for texturing
+    pub v: f64,
+    pub tangent: Vec3<f64>, // dp/du, how the point moves as u grows (not normalized); zero inside media
+    pub bitangent: Vec3<f64>,   // dp/dv, zero where the surface has no v
+    pub error: Vec3<f64>,   // conservative bound on the rounding error in point, per axis
+    pub index: usize,       // which part of a compound object was hit (particle of a SphereCloud, triangle of a TriangleMesh)
+    pub footprint: f64,     // width of the ray's cone where it hit, see set_footprint
+    pub spread: f64,        // and how fast that cone widens, handed on to rays spawned here
+    pub incidence: f64,     // |cos| of the angle between the ray and the surface, the footprint stretches by its inverse
     pub front_face: bool,   // specify if the normal is in the direction of the ray
 }
 
@@ -32,10 +77,64 @@ impl HitRecord{
             *outward_normal
         }else{
             -*outward_normal
-        }
+        };
+
+        // primitives with smooth shading overwrite normal afterwards, this one stays with the actual surface
+        self.geometric_normal = self.normal;
 
     }
 
+    // forgets the fields not every primitive fills in (uv, tangents, part index), so a record that is
+    // reused for another test cannot carry them over from an earlier hit
+    pub fn clear_surface(&mut self)
+    {
+        self.u = 0.0;
+        self.v = 0.0;
+        self.tangent = Vec3::origin();
+        self.bitangent = Vec3::origin();
+        self.index = 0;
+    }
+
+    // records how wide the ray's cone is at the hit; done once for the closest hit of a ray (Camera does it)
+    // rather than by every primitive, the transforms in between do not change t or the cone
+    pub fn set_footprint(&mut self, ray: &Ray)
+    {
+        self.footprint = ray.cone_width(self.t);
+        self.spread = ray.cone_spread();
+        self.incidence = ray.direction().normalize().dot(&self.geometric_normal).abs();
+    }
+
+    // how much of the texture (in uv units) the footprint covers, for picking a mip level
+    // isotropic: the larger of the two directions, with the cone stretched to its long side at grazing angles
+    // (which blurs the short side more than it needs), capped so a ray along the surface stays finite
+    pub fn uv_width(&self) -> f64
+    {
+        let width = self.footprint / self.incidence.max(MIN_INCIDENCE);
+        let across = |derivative: &Vec3<f64>| {
+            let length = derivative.mag();
+            if length > 0.0 { width / length } else { 0.0 }
+        };
+        across(&self.tangent).max(across(&self.bitangent))
+    }
+
+    // ray leaving the hit point, its origin pushed along the geometric normal just past the error bound on
+    // the side the direction points to, so it cannot find the surface it starts on again
+    // (a smoothed shading normal can lean far enough that its side is not the surface's side)
+    pub fn spawn_ray(&self, direction: Vec3<f64>, time: f64) -> Ray
+    {
+        let n = self.geometric_normal;
+        let distance = n.abs().dot(&self.error);
+        let offset = if direction.dot(&n) < 0.0 { -distance * n } else { distance * n };
+        let p = self.point + offset;
+
+        // round away from the surface so the offset is not lost to the addition
+        let nudge = |v: f64, o: f64| if o > 0.0 { v.next_up() } else if o < 0.0 { v.next_down() } else { v };
+        let origin = Vec3::new(nudge(*p.x(), *offset.x()), nudge(*p.y(), *offset.y()), nudge(*p.z(), *offset.z()));
+
+        // the cone carries on from its width here; how curvature or roughness would widen it is not tracked
+        Ray::new_with_time(origin, direction, time).with_cone(self.footprint, self.spread)
+    }
+
     pub fn default() -> Self
     {
         let def_vec = Vec3::new(0.0, 0.0, 0.0);
@@ -43,8 +142,130 @@ impl HitRecord{
             point: def_vec,
             mat: Rc::new(DefaultMaterial{}),
             normal:def_vec,
+            geometric_normal: def_vec,
+            u: 0.0,
+            v: 0.0,
+            tangent: def_vec,
+            bitangent: def_vec,
+            error: def_vec,
+            index: 0,
+            footprint: 0.0,
+            spread: 0.0,
+            incidence: 1.0,
             front_face: true // by default point against the ray?
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reports a root one ulp past wherever the search starts, like a root finder creeping along a tangent
+    struct Creeping;
+
+    impl Hittable for Creeping {
+        fn hit(&self, _ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+            hit_record.t = interval.min().next_up();
+            hit_record.t < interval.max()
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            Aabb::empty()
+        }
+    }
+
+    #[test]
+    fn hit_all_terminates_on_creeping_roots() {
+        let ray = Ray::new(Vec3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = Vec::new();
+        Creeping.hit_all(&ray, &Interval::new(0.0, 1.0), &mut hits);
+
+        assert!(!hits.is_empty());
+        assert!(hits.len() <= MAX_HITS);
+        assert!(hits.windows(2).all(|w| w[1].t > w[0].t));
+    }
+
+    #[test]
+    fn spawn_ray_steps_off_along_the_geometric_normal() {
+        // a surface at z = 0 seen from above, shaded with a normal leaning far over to +x
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut hit_record = HitRecord::default();
+        hit_record.set_face_normal(&ray, &Vec3::new(0.0, 0.0, 1.0));
+        hit_record.normal = Vec3::new(0.995, 0.0, 0.0998).normalize();
+        hit_record.error = Vec3::new(1e-6, 1e-6, 1e-6);
+
+        // leaves above the surface although it points against the shading normal
+        let reflected = hit_record.spawn_ray(Vec3::new(-1.0, 0.0, 0.05), 0.0);
+        assert!(*reflected.origin().z() > 1e-6);
+        assert_eq!(*reflected.origin().x(), 0.0);
+
+        // and below it when it goes through
+        let refracted = hit_record.spawn_ray(Vec3::new(0.5, 0.0, -1.0), 0.0);
+        assert!(*refracted.origin().z() < -1e-6);
+    }
+
+    // moving the hit point by h along dp/du should move u by h and leave v alone, and the other way round for dp/dv
+    fn assert_derivatives_follow_uv(name: &str, object: &dyn Hittable, origin: Vec3<f64>, target: Vec3<f64>) {
+        let everywhere = Interval::new(0.0, f64::INFINITY);
+        let mut hit_record = HitRecord::default();
+        assert!(object.hit(&Ray::new(origin, target - origin), &everywhere, &mut hit_record), "{name}: missed");
+
+        for (axis, derivative) in [("u", hit_record.tangent), ("v", hit_record.bitangent)] {
+            assert!(derivative.mag() > 0.0, "{name}: no dp/d{axis}");
+            assert!(derivative.dot(&hit_record.geometric_normal).abs() < 1e-9 * derivative.mag(), "{name}: dp/d{axis} leaves the surface");
+
+            let h = 1e-4 / derivative.mag();
+            let mut moved = HitRecord::default();
+            let nudged = hit_record.point + h * derivative;
+            assert!(object.hit(&Ray::new(origin, nudged - origin), &everywhere, &mut moved), "{name}: missed the nudged point");
+
+            let (du, dv) = (moved.u - hit_record.u, moved.v - hit_record.v);
+            let (along, across) = if axis == "u" { (du, dv) } else { (dv, du) };
+            assert!((along - h).abs() < 0.01 * h, "{name}: d{axis} {along} for a step of {h}");
+            assert!(across.abs() < 0.01 * h, "{name}: stepping along dp/d{axis} moved the other coordinate by {across}");
+        }
+    }
+
+    #[test]
+    fn derivatives_follow_uv() {
+        use crate::bilinear_patch::BilinearPatch;
+        use crate::cuboid::Cuboid;
+        use crate::mesh::{Mesh, TriangleMesh};
+        use crate::quad::Quad;
+        use crate::quadric::{Cone, Cylinder, Disk, Torus};
+        use crate::sphere::Sphere;
+        use crate::transform::RotateY;
+
+        let mat: Rc<dyn Material> = Rc::new(DefaultMaterial {});
+        let axis = Vec3::new(0.3, 1.0, -0.2);
+        let eye = Vec3::new(0.4, 0.7, 6.0);
+
+        // a quad whose uvs are turned and stretched against its edges, once as triangles and once as a patch
+        let mut mesh = Mesh::new(
+            vec![Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.2), Vec3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, 1.0, -0.1)],
+            vec![vec![0, 1, 2, 3]],
+        );
+        mesh.uvs = Some(vec![vec![[0.1, 0.0], [0.5, 0.3], [0.2, 0.9], [-0.2, 0.6]]]);
+
+        let sphere: Rc<dyn Hittable> = Rc::new(Sphere::new(Vec3::new(0.2, 0.1, 0.0), 1.5, Rc::clone(&mat)));
+        let objects: Vec<(&str, Rc<dyn Hittable>, Vec3<f64>)> = vec![
+            ("sphere", Rc::clone(&sphere), Vec3::new(0.5, 0.6, 0.0)),
+            ("rotated sphere", Rc::new(RotateY::new(sphere, 35.0)), Vec3::new(0.5, 0.6, 0.0)),
+            ("quad", Rc::new(Quad::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, 0.5, 0.0), Vec3::new(0.0, 2.0, 0.3), Rc::clone(&mat))), Vec3::new(0.3, 0.5, 0.1)),
+            ("cuboid", Rc::new(Cuboid::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 2.0, 0.5), Rc::clone(&mat))), Vec3::new(0.2, 0.3, 0.0)),
+            ("patch", Rc::new(BilinearPatch::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.5), Vec3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, 1.0, 0.4), Rc::clone(&mat))), Vec3::new(0.2, 0.3, 0.0)),
+            ("cylinder", Rc::new(Cylinder::new(Vec3::new(0.0, -1.0, 0.0), axis, 1.0, 2.0, false, Rc::clone(&mat))), Vec3::new(0.1, 0.2, 0.0)),
+            ("cone", Rc::new(Cone::new(Vec3::new(0.0, -1.0, 0.0), axis, 1.0, 2.0, false, Rc::clone(&mat))), Vec3::new(0.1, -0.3, 0.0)),
+            ("disk", Rc::new(Disk::new(Vec3::origin(), Vec3::new(0.2, 0.3, 1.0), 1.0, Rc::clone(&mat))), Vec3::new(0.3, -0.2, 0.0)),
+            ("torus", Rc::new(Torus::new(Vec3::origin(), Vec3::new(0.0, 0.3, 1.0), 1.0, 0.4, Rc::clone(&mat))), Vec3::new(0.9, 0.5, 0.0)),
+            ("triangles", Rc::new(TriangleMesh::new(&mesh, Rc::clone(&mat))), Vec3::new(0.2, -0.3, 0.0)),
+            ("mesh patch", Rc::new(TriangleMesh::with_patches(&mesh, Rc::clone(&mat))), Vec3::new(0.2, -0.3, 0.0)),
+        ];
+
+        for (name, object, target) in objects.iter() {
+            assert_derivatives_follow_uv(name, object.as_ref(), eye, *target);
+        }
+    }
+}
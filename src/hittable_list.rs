@@ -1,3 +1,4 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
 use crate::interval::Interval;
@@ -6,13 +7,14 @@ use std::rc::Rc;
 // the hittable list struct
 pub struct HittableList{
     objects: Vec<Rc<dyn Hittable>>, // dereference the Rc to get the underlying type
+    bbox: Aabb,                     // grows as objects are added
 }
 
 impl HittableList
 {
     pub fn new() -> Self
     {
-        Self{objects: Vec::new()}
+        Self{objects: Vec::new(), bbox: Aabb::empty()}
     }
 
     pub fn from(object: Rc<dyn Hittable>) -> Self{
@@ -24,10 +26,12 @@ impl HittableList
     pub fn clear(&mut self)
     {
         self.objects.clear();
+        self.bbox = Aabb::empty();
     }
 
     pub fn add(&mut self, object: Rc<dyn Hittable>)
     {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 
@@ -49,6 +53,8 @@ impl Hittable for HittableList
 
         for object in self.objects.iter()
         {
+            // the record is shared between objects, start each test from a clean one
+            temp_rec.clear_surface();
             if object.hit(ray, &Interval::new(interval.min(), closest_so_far), &mut temp_rec)
             {
                 hit_anything = true;
@@ -64,4 +70,56 @@ impl Hittable for HittableList
 
         hit_anything
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Vec3;
+
+    // a hit at a fixed t, optionally with uvs, a tangent and a part index
+    struct Wall {
+        t: f64,
+        textured: bool,
+    }
+
+    impl Hittable for Wall {
+        fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+            if !interval.surrounds(self.t) {
+                return false;
+            }
+            hit_record.t = self.t;
+            hit_record.point = ray.at(self.t);
+            hit_record.set_face_normal(ray, &Vec3::new(0.0, 0.0, 1.0));
+            if self.textured {
+                hit_record.u = 0.25;
+                hit_record.v = 0.75;
+                hit_record.tangent = Vec3::new(1.0, 0.0, 0.0);
+                hit_record.index = 3;
+            }
+            true
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            Aabb::empty()
+        }
+    }
+
+    #[test]
+    fn surface_fields_do_not_leak_between_objects() {
+        let mut list = HittableList::new();
+        list.add(Rc::new(Wall { t: 2.0, textured: true }));
+        list.add(Rc::new(Wall { t: 1.0, textured: false }));
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut record = HitRecord::default();
+        assert!(list.hit(&ray, &Interval::new(0.0, 10.0), &mut record));
+
+        assert_eq!(record.t, 1.0);
+        assert_eq!((record.u, record.v, record.index), (0.0, 0.0, 0));
+        assert_eq!(record.tangent, Vec3::origin());
+    }
+}
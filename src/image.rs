@@ -0,0 +1,473 @@
+use crate::core::Vec3;
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+// an rgb image held as floats, row 0 is the top of the picture
+// integer formats are scaled to [0, 1] as stored in the file (no gamma decoding), float formats are kept as is
+pub struct Image {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,     // rgb triples, row by row
+    hdr: bool,          // came from a float format, so the values are already linear radiance
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), width * height * 3, "image data does not match its size");
+        Self { width, height, data, hdr: false }
+    }
+
+    // reads PGM / PPM (P2, P3, P5, P6), PNG, PFM (Pf, PF) and radiance HDR files, the format is taken from the magic number
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = fs::read(path)?;
+
+        match bytes.get(..2) {
+            Some(b"P2") | Some(b"P3") | Some(b"P5") | Some(b"P6") => Self::parse_pnm(&bytes),
+            Some(b"Pf") | Some(b"PF") => Self::parse_pfm(&bytes),
+            Some(b"#?") => Self::parse_hdr(&bytes),
+            _ if bytes.starts_with(b"\x89PNG") => Self::parse_png(&bytes),
+            _ => Err(invalid("unsupported image format")),
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        self.hdr
+    }
+
+    // treats the stored values as srgb encoded and turns them into linear ones, what 8 bit photos need
+    // before they are lit; float images are left alone
+    pub fn srgb_to_linear(mut self) -> Self {
+        if !self.hdr {
+            for v in self.data.iter_mut() {
+                *v = if *v <= 0.04045 { *v / 12.92 } else { ((*v + 0.055) / 1.055).powf(2.4) };
+            }
+        }
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // pixel colour, coordinates outside the image are clamped to the border
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3<f64> {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        let i = (y * self.width + x) * 3;
+        Vec3::new(self.data[i] as f64, self.data[i + 1] as f64, self.data[i + 2] as f64)
+    }
+
+    // single value per pixel, the average of the three channels
+    pub fn gray(&self, x: usize, y: usize) -> f64 {
+        let p = self.pixel(x, y);
+        (p.x() + p.y() + p.z()) / 3.0
+    }
+
+    fn parse_pnm(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut header = HeaderReader { bytes, pos: 2 };
+        let rgb = bytes[1] == b'3' || bytes[1] == b'6';
+        let ascii = bytes[1] == b'2' || bytes[1] == b'3';
+
+        let width = header.number()?;
+        let height = header.number()?;
+        let max_value = header.number()?;
+        if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+            return Err(invalid("bad pnm header"));
+        }
+
+        let channels = if rgb { 3 } else { 1 };
+        let count = sample_count(width, height, channels)?;
+        let scale = 1.0 / max_value as f32;
+
+        let samples: Vec<f32> = if ascii {
+            (0..count).map(|_| header.number().map(|v| v as f32 * scale)).collect::<Result<_, _>>()?
+        } else {
+            // exactly one whitespace byte separates the header from the raster
+            let wide = max_value > 255;
+            let needed = if wide { count * 2 } else { count };
+            let raster = bytes.get(header.pos + 1..header.pos + 1 + needed)
+                .ok_or_else(|| invalid("pnm raster is truncated"))?;
+
+            if wide {
+                raster.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]]) as f32 * scale).collect()
+            } else {
+                raster.iter().map(|&v| v as f32 * scale).collect()
+            }
+        };
+
+        Ok(Self::new(width, height, expand_to_rgb(samples, channels)))
+    }
+
+    fn parse_pfm(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut header = HeaderReader { bytes, pos: 2 };
+        let channels = if bytes[1] == b'F' { 3 } else { 1 };
+
+        let width = header.number()?;
+        let height = header.number()?;
+        let scale = header.float()?;
+        if width == 0 || height == 0 {
+            return Err(invalid("bad pfm header"));
+        }
+
+        // a negative scale means little endian
+        let count = sample_count(width, height, channels)?;
+        let raster = bytes.get(header.pos + 1..header.pos + 1 + count * 4)
+            .ok_or_else(|| invalid("pfm raster is truncated"))?;
+
+        let mut samples = Vec::with_capacity(count);
+
+        // pfm rows are stored bottom to top
+        for y in (0..height).rev() {
+            let row = &raster[y * width * channels * 4..(y + 1) * width * channels * 4];
+            for c in row.chunks_exact(4) {
+                let b = [c[0], c[1], c[2], c[3]];
+                samples.push(if scale < 0.0 { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) });
+            }
+        }
+
+        Ok(Self { hdr: true, ..Self::new(width, height, expand_to_rgb(samples, channels)) })
+    }
+
+    fn parse_png(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut decoder = png::Decoder::new(bytes);
+
+        // palettes, transparency and bit depths under 8 all come out as plain 8 bit samples
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(|e| invalid(&format!("png: {e}")))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| invalid(&format!("png: {e}")))?;
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let channels = info.color_type.samples();
+        let samples: Vec<f32> = match info.bit_depth {
+            png::BitDepth::Sixteen => buf[..info.buffer_size()].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]]) as f32 / 65535.0).collect(),
+            _ => buf[..info.buffer_size()].iter().map(|&v| v as f32 / 255.0).collect(),
+        };
+
+        // alpha is dropped, only colour is kept
+        let samples = match channels {
+            2 => samples.chunks_exact(2).map(|c| c[0]).collect(),
+            4 => samples.chunks_exact(4).flat_map(|c| [c[0], c[1], c[2]]).collect(),
+            _ => samples,
+        };
+        Ok(Self::new(width, height, expand_to_rgb(samples, if channels < 3 { 1 } else { 3 })))
+    }
+
+    // radiance rgbe: a text header ended by an empty line, a resolution line, then rows of
+    // shared exponent pixels that are usually run length encoded one channel at a time
+    fn parse_hdr(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut pos = 0;
+        let mut line = || {
+            let start = pos;
+            while bytes.get(pos).is_some_and(|&b| b != b'\n') {
+                pos += 1;
+            }
+            if pos >= bytes.len() {
+                return Err(invalid("unexpected end of hdr header"));
+            }
+            pos += 1;
+            Ok(String::from_utf8_lossy(&bytes[start..pos - 1]).into_owned())
+        };
+
+        loop {
+            let header = line()?;
+            if header.trim().is_empty() {
+                break;
+            }
+            if header.starts_with("FORMAT=") && header.trim() != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid("only rgbe hdr files are supported"));
+            }
+        }
+
+        // only the usual top to bottom, left to right orientation
+        let resolution = line()?;
+        let fields: Vec<&str> = resolution.split_whitespace().collect();
+        let (height, width) = match fields[..] {
+            ["-Y", h, "+X", w] => (h.parse().ok(), w.parse().ok()),
+            _ => (None, None),
+        };
+        let (Some(height), Some(width)) = (height, width) else {
+            return Err(invalid("unsupported hdr resolution line"));
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid("bad hdr header"));
+        }
+
+        let mut raster = &bytes[pos..];
+        let mut data = Vec::with_capacity(width * height * 3);
+        let mut row = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            raster = read_hdr_row(raster, &mut row)?;
+            for rgbe in row.iter() {
+                let scale = if rgbe[3] == 0 { 0.0 } else { 2f32.powi(rgbe[3] as i32 - 136) };
+                data.extend(rgbe[..3].iter().map(|&c| (c as f32 + 0.5) * scale));
+            }
+        }
+
+        Ok(Self { hdr: true, ..Self::new(width, height, data) })
+    }
+}
+
+// one scanline of rgbe pixels, returns what is left of the raster
+fn read_hdr_row<'a>(raster: &'a [u8], row: &mut [[u8; 4]]) -> std::io::Result<&'a [u8]> {
+    let truncated = || invalid("hdr raster is truncated");
+    let width = row.len();
+
+    // new style rle rows start with 2 2 and the width, anything else is stored flat
+    let rle = (8..0x8000).contains(&width) && raster.len() >= 4 && raster[0] == 2 && raster[1] == 2 && raster[2] & 0x80 == 0;
+    if !rle {
+        let flat = raster.get(..width * 4).ok_or_else(truncated)?;
+        for (pixel, c) in row.iter_mut().zip(flat.chunks_exact(4)) {
+            pixel.copy_from_slice(c);
+        }
+        return Ok(&raster[width * 4..]);
+    }
+    if ((raster[2] as usize) << 8 | raster[3] as usize) != width {
+        return Err(invalid("hdr scanline width does not match the image"));
+    }
+
+    let mut pos = 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *raster.get(pos).ok_or_else(truncated)? as usize;
+            pos += 1;
+            if count > 128 {
+                // a run of one repeated value
+                let count = count - 128;
+                let value = *raster.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                if x + count > width {
+                    return Err(invalid("hdr run overflows its scanline"));
+                }
+                for pixel in row[x..x + count].iter_mut() {
+                    pixel[channel] = value;
+                }
+                x += count;
+            } else {
+                // count literal values
+                if count == 0 || x + count > width {
+                    return Err(invalid("bad hdr run length"));
+                }
+                let values = raster.get(pos..pos + count).ok_or_else(truncated)?;
+                for (pixel, &value) in row[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                pos += count;
+                x += count;
+            }
+        }
+    }
+    Ok(&raster[pos..])
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// width * height * channels, refusing sizes that could not be allocated anyway (4 bytes a sample at most)
+fn sample_count(width: usize, height: usize, channels: usize) -> std::io::Result<usize> {
+    width.checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .filter(|n| n.checked_mul(4).is_some_and(|bytes| bytes <= isize::MAX as usize))
+        .ok_or_else(|| invalid("image size overflows"))
+}
+
+fn expand_to_rgb(samples: Vec<f32>, channels: usize) -> Vec<f32> {
+    if channels == 3 {
+        return samples;
+    }
+    samples.iter().flat_map(|&v| [v, v, v]).collect()
+}
+
+// pulls whitespace separated tokens out of a netpbm style header, skipping # comments
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl HeaderReader<'_> {
+    fn token(&mut self) -> std::io::Result<&str> {
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'#') => {
+                    while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(invalid("unexpected end of image header")),
+            }
+        }
+
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| invalid("bad image header"))
+    }
+
+    fn number(&mut self) -> std::io::Result<usize> {
+        self.token()?.parse().map_err(|_| invalid("bad number in image header"))
+    }
+
+    fn float(&mut self) -> std::io::Result<f64> {
+        self.token()?.parse().map_err(|_| invalid("bad number in image header"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pnm(header: &str, raster: &[u8]) -> Vec<u8> {
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(raster);
+        bytes
+    }
+
+    #[test]
+    fn binary_ppm() {
+        let image = Image::parse_pnm(&pnm("P6\n# comment\n2 1\n255\n", &[255, 0, 0, 0, 51, 255])).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((image.pixel(1, 0) - Vec3::new(0.0, 0.2, 1.0)).mag() < 1e-6);
+        assert!(!image.is_hdr());
+    }
+
+    #[test]
+    fn ascii_and_sixteen_bit_pgm() {
+        let image = Image::parse_pnm(b"P2 2 2 4\n0 1\n2 4\n").unwrap();
+        assert_eq!(image.gray(1, 1), 1.0);
+        assert_eq!(image.pixel(0, 1), Vec3::new(0.5, 0.5, 0.5));
+
+        let image = Image::parse_pnm(&pnm("P5 1 1 65535\n", &[0x80, 0x00])).unwrap();
+        assert!((image.gray(0, 0) - 32768.0 / 65535.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn truncated_pnm_is_an_error() {
+        assert!(Image::parse_pnm(&pnm("P6 2 1 255\n", &[255, 0, 0, 0, 51])).is_err());
+        assert!(Image::parse_pnm(b"P5 1 1 255").is_err());
+        assert!(Image::parse_pnm(b"P2 2 2 4\n0 1\n2").is_err());
+        assert!(Image::parse_pnm(b"P6 2").is_err());
+        assert!(Image::parse_pnm(b"P6 99999999999 99999999999 255\n").is_err());
+    }
+
+    #[test]
+    fn pfm_rows_are_flipped_and_endianness_follows_the_scale() {
+        let mut little = b"Pf\n1 2\n-1.0\n".to_vec();
+        little.extend(0.25f32.to_le_bytes());
+        little.extend(4.0f32.to_le_bytes());
+        let image = Image::parse_pfm(&little).unwrap();
+        assert!(image.is_hdr());
+        assert_eq!(image.gray(0, 0), 4.0);
+        assert_eq!(image.gray(0, 1), 0.25);
+
+        let mut big = b"PF 1 1 1.0\n".to_vec();
+        for v in [1.0f32, 2.0, 3.0] {
+            big.extend(v.to_be_bytes());
+        }
+        assert_eq!(Image::parse_pfm(&big).unwrap().pixel(0, 0), Vec3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn truncated_pfm_is_an_error() {
+        let mut bytes = b"PF 1 1 -1.0\n".to_vec();
+        bytes.extend(1.0f32.to_le_bytes());
+        assert!(Image::parse_pfm(&bytes).is_err());
+        assert!(Image::parse_pfm(b"PF 1 1 -1.0").is_err());
+        assert!(Image::parse_pfm(b"Pf 1").is_err());
+    }
+
+    fn png(width: u32, height: u32, color: png::ColorType, depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn png_colour_grey_and_alpha() {
+        let image = Image::parse_png(&png(2, 1, png::ColorType::Rgb, png::BitDepth::Eight, &[255, 0, 0, 0, 0, 255])).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(image.pixel(1, 0), Vec3::new(0.0, 0.0, 1.0));
+
+        // alpha is dropped
+        let image = Image::parse_png(&png(1, 1, png::ColorType::Rgba, png::BitDepth::Eight, &[0, 255, 0, 7])).unwrap();
+        assert_eq!(image.pixel(0, 0), Vec3::new(0.0, 1.0, 0.0));
+
+        let image = Image::parse_png(&png(1, 2, png::ColorType::Grayscale, png::BitDepth::Sixteen, &[0xff, 0xff, 0, 0])).unwrap();
+        assert_eq!(image.gray(0, 0), 1.0);
+        assert_eq!(image.gray(0, 1), 0.0);
+    }
+
+    #[test]
+    fn truncated_png_is_an_error() {
+        let bytes = png(4, 4, png::ColorType::Rgb, png::BitDepth::Eight, &[90; 48]);
+        for length in [8, 20, bytes.len() / 2, bytes.len() - 13] {
+            assert!(Image::parse_png(&bytes[..length]).is_err(), "cut at {length} bytes");
+        }
+    }
+
+    const HDR_HEADER: &[u8] = b"#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\n\n";
+
+    fn hdr(resolution: &str, raster: &[u8]) -> Vec<u8> {
+        let mut bytes = HDR_HEADER.to_vec();
+        bytes.extend_from_slice(resolution.as_bytes());
+        bytes.extend_from_slice(raster);
+        bytes
+    }
+
+    #[test]
+    fn flat_hdr() {
+        // exponent 136 scales the mantissa by one, each channel reads as its middle (value + 0.5)
+        let image = Image::parse_hdr(&hdr("-Y 1 +X 2\n", &[1, 2, 3, 136, 0, 0, 0, 0])).unwrap();
+        assert!(image.is_hdr());
+        assert_eq!(image.pixel(0, 0), Vec3::new(1.5, 2.5, 3.5));
+        assert_eq!(image.pixel(1, 0), Vec3::origin());
+    }
+
+    #[test]
+    fn run_length_encoded_hdr() {
+        // red as one run, green as literals, blue as a run of zeros, then the exponent
+        let mut row = vec![2, 2, 0, 8];
+        row.extend([128 + 8, 10]);
+        row.push(8);
+        row.extend(0..8);
+        row.extend([128 + 8, 0, 128 + 8, 136]);
+
+        let image = Image::parse_hdr(&hdr("-Y 1 +X 8\n", &row)).unwrap();
+        for x in 0..8 {
+            assert_eq!(image.pixel(x, 0), Vec3::new(10.5, x as f64 + 0.5, 0.5));
+        }
+
+        // cut anywhere the row comes up short
+        for length in 0..row.len() {
+            assert!(Image::parse_hdr(&hdr("-Y 1 +X 8\n", &row[..length])).is_err(), "cut at {length} bytes");
+        }
+    }
+
+    #[test]
+    fn malformed_hdr_is_an_error() {
+        assert!(Image::parse_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n").is_err());
+        assert!(Image::parse_hdr(&hdr("-Y 1 +X 2", &[])).is_err());
+        assert!(Image::parse_hdr(&hdr("+Y 1 +X 2\n", &[0; 8])).is_err());
+        assert!(Image::parse_hdr(&hdr("-Y 0 +X 2\n", &[])).is_err());
+        assert!(Image::parse_hdr(&hdr("-Y 1 +X 2\n", &[1, 2, 3, 136, 0])).is_err());
+
+        let mut header = b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n".to_vec();
+        header.extend_from_slice(b"-Y 1 +X 1\n\0\0\0\0");
+        assert!(Image::parse_hdr(&header).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::INFINITY;
 
+#[derive(Clone, Copy)]
 pub struct Interval{
     min: f64,
     max: f64,
@@ -40,6 +41,19 @@ impl Interval{
         self.min < x && x < self.max
     }
 
+    // grow the interval by delta, split evenly between both ends
+    pub fn expand(&self, delta: f64) -> Interval
+    {
+        let padding = delta / 2.0;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
+    // the smallest interval enclosing both a and b
+    pub fn enclosing(a: &Interval, b: &Interval) -> Interval
+    {
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
     pub fn clamp(&self, x: f64) -> f64
     {
         if x < self.min
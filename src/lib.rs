@@ -1,6 +1,7 @@
 use std::fs::File;
 use std::io::Write;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 pub mod core;
 pub mod color;
@@ -11,6 +12,32 @@ pub mod hittable_list;
 pub mod interval;
 pub mod camera;
 pub mod material;
+pub mod transform;
+pub mod constant_medium;
+pub mod aabb;
+pub mod voxel_volume;
+pub mod csg;
+pub mod sdf;
+pub mod sdf_object;
+pub mod onb;
+pub mod quadric;
+pub mod image;
+pub mod heightfield;
+pub mod quad;
+pub mod cuboid;
+pub mod scenes;
+pub mod curve;
+pub mod bvh;
+pub mod mesh;
+pub mod subdivision;
+pub mod displacement;
+pub mod sphere_cloud;
+pub mod bilinear_patch;
+pub mod metaballs;
+pub mod texture;
+pub mod perlin;
+pub(crate) mod microfacet;
+pub mod principled;
 
 pub use core::{Vec3, INFINITY, PI};
 pub use color::write_color;
@@ -20,12 +47,43 @@ pub use hittable::HitRecord;
 pub use sphere::Sphere;
 pub use hittable_list::HittableList;
 pub use interval::Interval;
-pub use camera::Camera;
+pub use camera::{Camera, Background};
 pub use material::Material;
 pub use material::DefaultMaterial;
 pub use material::Dielectric;
+pub use material::RoughDielectric;
+pub use material::Layered;
+pub use material::MixMaterial;
 pub use material::Lambertian;
+pub use material::OrenNayar;
 pub use material::Metal;
+pub use material::Conductor;
+pub use material::Isotropic;
+pub use material::DiffuseLight;
+pub use transform::{Translate, RotateY};
+pub use constant_medium::ConstantMedium;
+pub use aabb::Aabb;
+pub use voxel_volume::{VoxelGrid, VoxelMedium};
+pub use csg::{Csg, CsgOp};
+pub use sdf::Sdf;
+pub use sdf_object::SdfObject;
+pub use onb::Onb;
+pub use quadric::{Cylinder, Cone, Disk, Torus};
+pub use image::Image;
+pub use heightfield::Heightfield;
+pub use quad::Quad;
+pub use cuboid::Cuboid;
+pub use curve::{Curve, CurveMode, Strands};
+pub use bvh::Bvh;
+pub use mesh::{Mesh, TriangleMesh};
+pub use subdivision::{subdivide, SubdivisionScheme};
+pub use displacement::{displace, EdgeTarget};
+pub use sphere_cloud::SphereCloud;
+pub use bilinear_patch::BilinearPatch;
+pub use metaballs::{Blob, Metaballs};
+pub use texture::{Texture, SolidColor, Checker, UvChecker, ImageTexture, WrapMode, NoiseTexture, Marble, Wood};
+pub use perlin::Perlin;
+pub use principled::Principled;
 
 
 // util functions
@@ -43,6 +101,25 @@ pub fn random_float_interval(min: f64, max: f64) -> f64
     min + (max - min)*random_float()
 }
 
+// continuous uniform number in [0, 1), for sampling that random_float's 1001 steps (and its 1.0) would bias
+pub fn random_canonical() -> f64
+{
+    rand::rng().random::<f64>()
+}
+
+// a generator that gives the same sequence for the same seed, for things that must look the same
+// on every render (noise tables and the like) rather than vary per sample
+pub fn seeded_rng(seed: u64) -> StdRng
+{
+    StdRng::seed_from_u64(seed)
+}
+
+// bound on the relative rounding error of n chained floating point operations (pbrt's gamma_n)
+pub fn gamma(n: u32) -> f64 {
+    let e = n as f64 * f64::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
 pub fn write_to_file(filename: &str, data: &str) {
     let mut file = File::create(filename).expect("Unable to create file");
     file.write_all(data.as_bytes()).expect("Unable to write data");
@@ -1,4 +1,9 @@
-use crate::{core::Vec3, random_float, HitRecord, Ray};
+use crate::{core::Vec3, random_canonical, random_float, HitRecord, Ray};
+use crate::texture::{SolidColor, Texture};
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, reflect, refract, Ggx};
+use crate::onb::Onb;
+
+use std::rc::Rc;
 
 // personally feel there should be a universal materical class fr
 
@@ -9,6 +14,12 @@ pub trait Material
     {
         false
     }
+
+    // light given off at the hit point, most materials emit nothing
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Vec3<f64>
+    {
+        Vec3::origin()
+    }
 }
 
 // this is for default value impl, im only using this to compile
@@ -23,16 +34,19 @@ impl Material for DefaultMaterial{
 // albedo defines a sort of fractional reflectance, the percentage of rays reflected/absorbed off a body
 pub struct Lambertian
 {
-    albedo: Vec3<f64>,
+    tex: Rc<dyn Texture>,
 }
 
 impl Lambertian
 {
     pub fn new(albedo: Vec3<f64>) -> Self
     {
-        Self{
-            albedo: albedo
-        }
+        Self::with_texture(Rc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(tex: Rc<dyn Texture>) -> Self
+    {
+        Self{tex}
     }
 }
 
@@ -47,28 +61,82 @@ impl Material for Lambertian
             scatter_direction = hit_record.normal;
         }
         // apparently we're passing these values in to be changed
-        *scattered = Ray::new(hit_record.point, scatter_direction);
-        *attenuation = self.albedo.clone();
+        *scattered = hit_record.spawn_ray(scatter_direction, ray_in.time());
+        *attenuation = self.tex.value_at(hit_record);
+        true
+    }
+}
+
+// rough diffuse surface made of lambertian v-grooves (oren-nayar, qualitative model):
+// compared to Lambertian it is flatter, brighter back towards the light and darker at grazing angles
+// sigma is the standard deviation of the groove slopes in degrees, 0 is plain Lambertian
+pub struct OrenNayar
+{
+    tex: Rc<dyn Texture>,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar
+{
+    pub fn new(albedo: Vec3<f64>, sigma: f64) -> Self
+    {
+        Self::with_texture(Rc::new(SolidColor::new(albedo)), sigma)
+    }
+
+    pub fn with_texture(tex: Rc<dyn Texture>, sigma: f64) -> Self
+    {
+        let sigma = crate::degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+        Self{tex, a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)), b: 0.45 * sigma2 / (sigma2 + 0.09)}
+    }
+}
+
+impl Material for OrenNayar
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        let frame = Onb::new(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let wi = Vec3::random_cosine_direction();
+
+        // sin(alpha) tan(beta), alpha the larger of the two angles to the normal and beta the smaller
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let cos_o = wo.z().abs();
+        let cos_i = *wi.z();
+        let sin_alpha_tan_beta = if cos_i > cos_o { sin_o * sin_i / cos_i } else { sin_i * sin_o / cos_o.max(1e-8) };
+
+        // cosine of the azimuth between the two directions
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 { (wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o) } else { 0.0 };
+
+        // cosine sampling cancels the cosine and 1 / pi out of the weight
+        *scattered = hit_record.spawn_ray(frame.transform(&wi), ray_in.time());
+        *attenuation = self.tex.value_at(hit_record) * (self.a + self.b * cos_phi.max(0.0) * sin_alpha_tan_beta);
         true
     }
 }
 
 pub struct Metal
 {
-    albedo: Vec3<f64>,
+    tex: Rc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal
 {
     pub fn new(albedo: Vec3<f64>, fuzz: f64) -> Self
+    {
+        Self::with_texture(Rc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn with_texture(tex: Rc<dyn Texture>, fuzz: f64) -> Self
     {
         // this implements a sphere which causes displacement of the reflection vectors
         let fuzz = match fuzz < 1.0 {
             true => fuzz,
             false => 1.0
         };
-        Self {albedo: albedo, fuzz: fuzz}
+        Self {tex, fuzz}
     }
 }
 
@@ -82,14 +150,89 @@ impl Material for Metal
         // we need to scale (to unit vector) each fuzz sphere to be consistent when compared with the reflection vector
         reflected = reflected.normalize() + (self.fuzz * Vec3::random_unit_vector());
 
-        *scattered = Ray::new(hit_record.point, reflected);
-        *attenuation = self.albedo.clone();
+        *scattered = hit_record.spawn_ray(reflected, ray_in.time());
+        *attenuation = self.tex.value_at(hit_record);
 
         // if we are scatter below thw surface because of a big sphere or a surface ray, simply absorb it
         hit_record.normal.dot(&scattered.direction()) > 0.0
     }
 }
 
+// rough metal: ggx microfacets with smith masking-shadowing and the exact fresnel term of a
+// conductor with complex index of refraction eta + i k (per rgb channel)
+// roughness is perceptual in [0, 1]; anisotropic surfaces stretch the highlight along the hit's tangent
+pub struct Conductor
+{
+    eta: Vec3<f64>,
+    k: Vec3<f64>,
+    ggx: Ggx,
+}
+
+impl Conductor
+{
+    pub fn new(eta: Vec3<f64>, k: Vec3<f64>, roughness: f64) -> Self
+    {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    // roughness_u along the tangent, roughness_v across it
+    pub fn anisotropic(eta: Vec3<f64>, k: Vec3<f64>, roughness_u: f64, roughness_v: f64) -> Self
+    {
+        Self{eta, k, ggx: Ggx::new(roughness_u, roughness_v)}
+    }
+
+    // measured indices sampled at red 650nm, green 550nm and blue 450nm
+    pub fn gold(roughness: f64) -> Self
+    {
+        Self::new(Vec3::new(0.143, 0.374, 1.442), Vec3::new(3.983, 2.385, 1.603), roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self
+    {
+        Self::new(Vec3::new(0.155, 0.116, 0.138), Vec3::new(4.828, 3.122, 2.147), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self
+    {
+        Self::new(Vec3::new(0.200, 0.924, 1.102), Vec3::new(3.912, 2.452, 2.142), roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self
+    {
+        Self::new(Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    pub fn iron(roughness: f64) -> Self
+    {
+        Self::new(Vec3::new(2.912, 2.950, 2.585), Vec3::new(3.089, 2.932, 2.767), roughness)
+    }
+}
+
+impl Material for Conductor
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        let frame = Onb::with_tangent(&hit_record.normal, &hit_record.tangent);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if *wo.z() <= 0.0
+        {
+            return false;
+        }
+
+        // reflect off a microfacet picked among those visible from wo, then the sample weight
+        // brdf * cos / pdf reduces to fresnel times the share of light not shadowed on the way out
+        let m = self.ggx.sample_visible(&wo, random_canonical(), random_canonical());
+        let wi = reflect(&wo, &m);
+        if *wi.z() <= 0.0
+        {
+            return false;
+        }
+
+        *attenuation = fresnel_conductor(wo.dot(&m), &self.eta, &self.k) * (self.ggx.g(&wo, &wi) / self.ggx.g1(&wo));
+        *scattered = hit_record.spawn_ray(frame.transform(&wi), ray_in.time());
+        true
+    }
+}
+
 pub struct Dielectric
 {
     refraction_index: f64,
@@ -143,13 +286,314 @@ impl Material for Dielectric
             direction = Vec3::refract(&unit_direction, &hit_record.normal, ref_index);
         }
 
-        *scattered = Ray::new(hit_record.point, direction);
+        *scattered = hit_record.spawn_ray(direction, ray_in.time());
         true
     }
 }
 
+// frosted glass: ggx microfacets that both reflect and transmit (walter et al. 2007)
+// each microfacet acts as a small smooth interface, so which way a ray goes is picked by its fresnel term
+// roughness is perceptual in [0, 1], zero behaves like Dielectric
+pub struct RoughDielectric
+{
+    refraction_index: f64,
+    ggx: Ggx,
+}
 
+impl RoughDielectric
+{
+    pub fn new(refraction_index: f64, roughness: f64) -> Self
+    {
+        Self{refraction_index, ggx: Ggx::new(roughness, roughness)}
+    }
+}
+
+impl Material for RoughDielectric
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        // the normal always faces the incoming ray, front_face says whether that is from outside
+        let eta = match hit_record.front_face {
+            true => self.refraction_index,
+            false => 1.0 / self.refraction_index,
+        };
+
+        let frame = Onb::with_tangent(&hit_record.normal, &hit_record.tangent);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if *wo.z() <= 0.0
+        {
+            return false;
+        }
+
+        let m = self.ggx.sample_visible(&wo, random_canonical(), random_canonical());
+        let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+
+        // reflect with probability fresnel, which cancels it out of the sample weight
+        let wi = match refract(&wo, &m, eta) {
+            Some(wt) if random_canonical() >= fresnel => wt,
+            _ => reflect(&wo, &m),
+        };
 
+        // a reflection has to stay on this side and a transmission has to cross over, anything
+        // else went through the microsurface the wrong way and is lost to masking
+        let transmitted = *wi.z() < 0.0;
+        if transmitted == (wo.dot(&m) * wi.dot(&m) > 0.0)
+        {
+            return false;
+        }
+
+        *attenuation = Vec3::new(1.0, 1.0, 1.0) * (self.ggx.g(&wo, &wi) / self.ggx.g1(&wo));
+        *scattered = hit_record.spawn_ray(frame.transform(&wi), ray_in.time());
+        true
+    }
+}
 
+// a clear dielectric coat (varnish, lacquer, car paint) over any other material
+// a ray either reflects off the rough coat (with fresnel probability) or refracts in, is tinted on
+// its way down, scatters off the base, is tinted again on the way up and tries to leave; whatever the
+// coat reflects back inside goes down to the base again, up to MAX_BOUNCES times
+// the tint is the colour left after crossing the coat straight through once per unit of thickness
+pub struct Layered
+{
+    base: Rc<dyn Material>,
+    refraction_index: f64,
+    ggx: Ggx,
+    tint: Vec3<f64>,
+    thickness: f64,
+}
 
+impl Layered
+{
+    const MAX_BOUNCES: u32 = 8;
 
+    pub fn new(base: Rc<dyn Material>, refraction_index: f64, roughness: f64) -> Self
+    {
+        Self{base, refraction_index, ggx: Ggx::new(roughness, roughness), tint: Vec3::new(1.0, 1.0, 1.0), thickness: 0.0}
+    }
+
+    pub fn with_absorption(self, tint: Vec3<f64>, thickness: f64) -> Self
+    {
+        Self{tint, thickness, ..self}
+    }
+
+    // what is left after crossing the coat along local direction w
+    fn transmittance(&self, w: &Vec3<f64>) -> Vec3<f64>
+    {
+        if self.thickness == 0.0
+        {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        let length = self.thickness / w.z().abs().max(1e-6);
+        Vec3::new(self.tint.x().powf(length), self.tint.y().powf(length), self.tint.z().powf(length))
+    }
+}
+
+// turns the frame upside down (a half turn about x), so a ray inside the coat sees the interface from above
+fn flip(w: &Vec3<f64>) -> Vec3<f64>
+{
+    Vec3::new(*w.x(), -w.y(), -w.z())
+}
+
+impl Material for Layered
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        // the coat is on the outside, a ray from within only meets the base
+        if !hit_record.front_face
+        {
+            return self.base.scatter(ray_in, hit_record, attenuation, scattered);
+        }
+
+        let frame = Onb::with_tangent(&hit_record.normal, &hit_record.tangent);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if *wo.z() <= 0.0
+        {
+            return false;
+        }
+
+        // the coat surface: reflect with probability fresnel, otherwise go in
+        let m = self.ggx.sample_visible(&wo, random_canonical(), random_canonical());
+        let fresnel = fresnel_dielectric(wo.dot(&m), self.refraction_index);
+        let mut down = match refract(&wo, &m, self.refraction_index) {
+            Some(wt) if random_canonical() >= fresnel => wt,
+            _ => {
+                let wi = reflect(&wo, &m);
+                if *wi.z() <= 0.0
+                {
+                    return false;
+                }
+                *attenuation = Vec3::new(1.0, 1.0, 1.0) * (self.ggx.g(&wo, &wi) / self.ggx.g1(&wo));
+                *scattered = hit_record.spawn_ray(frame.transform(&wi), ray_in.time());
+                return true;
+            }
+        };
+        if *down.z() >= 0.0
+        {
+            return false;
+        }
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0) * (self.ggx.g(&wo, &down) / self.ggx.g1(&wo));
+
+        for _ in 0..Self::MAX_BOUNCES
+        {
+            // down through the coat and off the base, which only needs the direction the ray arrives from
+            throughput = throughput * self.transmittance(&down);
+            let to_base = Ray::new_with_time(hit_record.point, frame.transform(&down), ray_in.time());
+            let mut base_attenuation = Vec3::origin();
+            let mut base_scattered = Ray::new(Vec3::origin(), Vec3::origin());
+            if !self.base.scatter(&to_base, hit_record, &mut base_attenuation, &mut base_scattered)
+            {
+                return false;
+            }
+            throughput = throughput * base_attenuation;
+
+            // a base that transmits would leave the coat's reach, treat it as absorbed
+            let up = frame.to_local(&base_scattered.direction().normalize());
+            if *up.z() <= 0.0
+            {
+                return false;
+            }
+            throughput = throughput * self.transmittance(&up);
+
+            // the coat from below, in the flipped frame where the inside is above the interface
+            let v = flip(&-up);
+            let m = self.ggx.sample_visible(&v, random_canonical(), random_canonical());
+            let fresnel = fresnel_dielectric(v.dot(&m), 1.0 / self.refraction_index);
+            match refract(&v, &m, 1.0 / self.refraction_index) {
+                Some(wt) if random_canonical() >= fresnel => {
+                    if *wt.z() >= 0.0
+                    {
+                        return false;
+                    }
+                    *attenuation = throughput * (self.ggx.g(&v, &wt) / self.ggx.g1(&v));
+                    *scattered = hit_record.spawn_ray(frame.transform(&flip(&wt)), ray_in.time());
+                    return true;
+                }
+                _ => {
+                    // reflected back inside, down to the base again
+                    let wr = reflect(&v, &m);
+                    if *wr.z() <= 0.0
+                    {
+                        return false;
+                    }
+                    throughput *= self.ggx.g(&v, &wr) / self.ggx.g1(&v);
+                    down = flip(&wr);
+                }
+            }
+        }
+        false
+    }
+
+    // light from the base is absorbed on its way out through the coat, like light that scattered off it
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        let emitted = self.base.emitted(ray_in, hit_record);
+        if !hit_record.front_face
+        {
+            return emitted;
+        }
+        let frame = Onb::with_tangent(&hit_record.normal, &hit_record.tangent);
+        emitted * self.transmittance(&frame.to_local(&-ray_in.direction().normalize()))
+    }
+}
+
+// blend of two materials: each hit uses second with probability weight and first otherwise, so on
+// average the surface looks like (1 - weight) * first + weight * second
+// the weight can come from a texture (Texture::scalar) to paint one material over the other
+pub struct MixMaterial
+{
+    first: Rc<dyn Material>,
+    second: Rc<dyn Material>,
+    weight: Rc<dyn Texture>,
+}
+
+impl MixMaterial
+{
+    pub fn new(first: Rc<dyn Material>, second: Rc<dyn Material>, weight: f64) -> Self
+    {
+        Self::with_texture(first, second, Rc::new(SolidColor::from_rgb(weight, weight, weight)))
+    }
+
+    pub fn with_texture(first: Rc<dyn Material>, second: Rc<dyn Material>, weight: Rc<dyn Texture>) -> Self
+    {
+        Self{first, second, weight}
+    }
+
+    fn weight(&self, hit_record: &HitRecord) -> f64
+    {
+        self.weight.scalar_at(hit_record).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        if random_canonical() < self.weight(hit_record)
+        {
+            self.second.scatter(ray_in, hit_record, attenuation, scattered)
+        }
+        else
+        {
+            self.first.scatter(ray_in, hit_record, attenuation, scattered)
+        }
+    }
+
+    // emission is blended directly, there is no need to pick
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        let weight = self.weight(hit_record);
+        (1.0 - weight) * self.first.emitted(ray_in, hit_record) + weight * self.second.emitted(ray_in, hit_record)
+    }
+}
+
+// phase function for participating media: scatters uniformly in every direction
+pub struct Isotropic
+{
+    tex: Rc<dyn Texture>,
+}
+
+impl Isotropic
+{
+    pub fn new(albedo: Vec3<f64>) -> Self
+    {
+        Self::with_texture(Rc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(tex: Rc<dyn Texture>) -> Self
+    {
+        Self{tex}
+    }
+}
+
+impl Material for Isotropic
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        *scattered = hit_record.spawn_ray(Vec3::random_unit_vector(), ray_in.time());
+        *attenuation = self.tex.value_at(hit_record);
+        true
+    }
+}
+
+// area light: gives off a constant colour and does not scatter anything
+pub struct DiffuseLight
+{
+    tex: Rc<dyn Texture>,
+}
+
+impl DiffuseLight
+{
+    pub fn new(emit: Vec3<f64>) -> Self
+    {
+        Self::with_texture(Rc::new(SolidColor::new(emit)))
+    }
+
+    pub fn with_texture(tex: Rc<dyn Texture>) -> Self
+    {
+        Self{tex}
+    }
+}
+
+impl Material for DiffuseLight
+{
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        self.tex.value_at(hit_record)
+    }
+}
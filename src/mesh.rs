@@ -0,0 +1,453 @@
+use crate::aabb::Aabb;
+use crate::bilinear_patch::{fill_patch_hit, intersect_patch, patch_bounds};
+use crate::bvh::Bvh;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::rc::Rc;
+
+// polygon mesh as it comes out of a modelling tool, before it is turned into triangles
+// faces index into positions and wind counter-clockwise seen from the outside
+// uvs and normals are stored per face corner so seams survive, creases mark sharp edges for subdivision
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3<f64>>,
+    pub faces: Vec<Vec<usize>>,
+    pub uvs: Option<Vec<Vec<[f64; 2]>>>,
+    pub normals: Option<Vec<Vec<Vec3<f64>>>>,
+    pub creases: Vec<(usize, usize, f64)>,     // edge (a, b) and its sharpness, infinity for a hard edge
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3<f64>>, faces: Vec<Vec<usize>>) -> Self {
+        for face in faces.iter() {
+            assert!(face.len() >= 3, "a face needs at least 3 vertices");
+            assert!(face.iter().all(|&i| i < positions.len()), "face index out of range");
+        }
+        Self { positions, faces, uvs: None, normals: None, creases: Vec::new() }
+    }
+
+    // marks the edge between positions a and b as a crease
+    // the sharpness is the number of subdivision levels it stays sharp for, fractional values blend
+    pub fn add_crease(&mut self, a: usize, b: usize, sharpness: f64) {
+        self.creases.push((a, b, sharpness));
+    }
+
+    // reads the v / vt / vn / f records of a wavefront obj file, everything else is ignored
+    // faces may be any polygon and use the v, v/vt, v//vn and v/vt/vn forms with negative indices allowed
+    // uvs and normals are only kept when every face has them
+    pub fn load_obj(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse_obj(&fs::read_to_string(path)?)
+    }
+
+    fn parse_obj(text: &str) -> std::io::Result<Self> {
+        let mut positions = Vec::new();
+        let mut tex_coords: Vec<[f64; 2]> = Vec::new();
+        let mut normals = Vec::new();
+        let mut faces = Vec::new();
+        let mut face_uvs = Vec::new();
+        let mut face_normals = Vec::new();
+        let mut all_uvs = true;
+        let mut all_normals = true;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("obj line {}: {}", line_number + 1, msg));
+
+            let mut fields = line.split_whitespace();
+            let kind = fields.next().unwrap_or_default();
+            let fields: Vec<&str> = fields.collect();
+
+            let numbers = |count: usize| -> std::io::Result<Vec<f64>> {
+                if fields.len() < count {
+                    return Err(invalid("too few numbers"));
+                }
+                fields[..count].iter().map(|f| f.parse::<f64>().map_err(|_| invalid("bad number"))).collect()
+            };
+
+            match kind {
+                "v" => {
+                    let n = numbers(3)?;
+                    positions.push(Vec3::new(n[0], n[1], n[2]));
+                }
+                "vt" => {
+                    let n = numbers(2)?;
+                    tex_coords.push([n[0], n[1]]);
+                }
+                "vn" => {
+                    let n = numbers(3)?;
+                    normals.push(Vec3::new(n[0], n[1], n[2]).normalize());
+                }
+                "f" => {
+                    if fields.len() < 3 {
+                        return Err(invalid("a face needs at least 3 vertices"));
+                    }
+
+                    // obj indices start at 1, negative ones count back from the latest entry
+                    let resolve = |field: &str, count: usize| -> std::io::Result<Option<usize>> {
+                        if field.is_empty() {
+                            return Ok(None);
+                        }
+                        let i: i64 = field.parse().map_err(|_| invalid("bad index"))?;
+                        let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+                        if resolved < 0 || resolved >= count as i64 {
+                            return Err(invalid("index out of range"));
+                        }
+                        Ok(Some(resolved as usize))
+                    };
+
+                    let mut face = Vec::with_capacity(fields.len());
+                    let mut corner_uvs = Vec::with_capacity(fields.len());
+                    let mut corner_normals = Vec::with_capacity(fields.len());
+
+                    for field in fields.iter() {
+                        let mut parts = field.split('/');
+                        let v = resolve(parts.next().unwrap_or_default(), positions.len())?.ok_or_else(|| invalid("missing vertex index"))?;
+                        let vt = resolve(parts.next().unwrap_or_default(), tex_coords.len())?;
+                        let vn = resolve(parts.next().unwrap_or_default(), normals.len())?;
+
+                        face.push(v);
+                        match vt {
+                            Some(vt) => corner_uvs.push(tex_coords[vt]),
+                            None => all_uvs = false,
+                        }
+                        match vn {
+                            Some(vn) => corner_normals.push(normals[vn]),
+                            None => all_normals = false,
+                        }
+                    }
+
+                    faces.push(face);
+                    face_uvs.push(corner_uvs);
+                    face_normals.push(corner_normals);
+                }
+                _ => {}
+            }
+        }
+
+        let mut mesh = Self::new(positions, faces);
+        if all_uvs && !tex_coords.is_empty() {
+            mesh.uvs = Some(face_uvs);
+        }
+        if all_normals && !normals.is_empty() {
+            mesh.normals = Some(face_normals);
+        }
+        Ok(mesh)
+    }
+
+    // the quads and everything else as two meshes over the same positions
+    pub fn split_quads(&self) -> (Self, Self) {
+        let pick = |quads: bool| {
+            let keep: Vec<bool> = self.faces.iter().map(|f| (f.len() == 4) == quads).collect();
+            Self {
+                positions: self.positions.clone(),
+                faces: select(&self.faces, &keep),
+                uvs: self.uvs.as_ref().map(|uvs| select(uvs, &keep)),
+                normals: self.normals.as_ref().map(|normals| select(normals, &keep)),
+                creases: self.creases.clone(),
+            }
+        };
+        (pick(true), pick(false))
+    }
+
+    // splits every polygon into a fan of triangles around its first corner
+    pub fn triangulate(&self) -> Self {
+        Self {
+            positions: self.positions.clone(),
+            faces: fan(&self.faces),
+            uvs: self.uvs.as_ref().map(|uvs| fan(uvs)),
+            normals: self.normals.as_ref().map(|normals| fan(normals)),
+            creases: self.creases.clone(),
+        }
+    }
+}
+
+// per face values of the faces marked in keep
+fn select<T: Clone>(values: &[Vec<T>], keep: &[bool]) -> Vec<Vec<T>> {
+    values.iter().zip(keep.iter()).filter(|(_, k)| **k).map(|(v, _)| v.clone()).collect()
+}
+
+// per face values split the same way Mesh::triangulate splits the faces
+fn fan<T: Copy>(faces: &[Vec<T>]) -> Vec<Vec<T>> {
+    faces.iter().flat_map(|values| (1..values.len() - 1).map(|i| vec![values[0], values[i], values[i + 1]])).collect()
+}
+
+// a mesh ready for rendering: triangles with optional per corner uvs and normals, kept in an internal bvh
+// without normals the triangles shade flat, without uvs a triangle's barycentric coordinates are used
+// with_patches keeps quads as bilinear patches instead, which follows non-planar quads without a seam
+pub struct TriangleMesh {
+    positions: Vec<Vec3<f64>>,
+    triangles: Vec<[usize; 3]>,
+    uvs: Option<Vec<[[f64; 2]; 3]>>,
+    normals: Option<Vec<[Vec3<f64>; 3]>>,
+    patches: Vec<[usize; 4]>,                   // corners in face order, primitive index = triangles.len() + i
+    patch_uvs: Option<Vec<[[f64; 2]; 4]>>,
+    patch_normals: Option<Vec<[Vec3<f64>; 4]>>,
+    bvh: Bvh,
+    mat: Rc<dyn Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: &Mesh, mat: Rc<dyn Material>) -> Self {
+        Self::build(&mesh.triangulate(), &Mesh::default(), mat)
+    }
+
+    pub fn with_patches(mesh: &Mesh, mat: Rc<dyn Material>) -> Self {
+        let (quads, others) = mesh.split_quads();
+        Self::build(&others.triangulate(), &quads, mat)
+    }
+
+    fn build(triangle_mesh: &Mesh, patch_mesh: &Mesh, mat: Rc<dyn Material>) -> Self {
+        let positions = triangle_mesh.positions.clone();
+        let triangles: Vec<[usize; 3]> = triangle_mesh.faces.iter().map(|f| [f[0], f[1], f[2]]).collect();
+        let uvs = triangle_mesh.uvs.as_ref().map(|uvs| uvs.iter().map(|c| [c[0], c[1], c[2]]).collect());
+        let normals = triangle_mesh.normals.as_ref().map(|normals| normals.iter().map(|c| [c[0], c[1], c[2]]).collect());
+
+        let patches: Vec<[usize; 4]> = patch_mesh.faces.iter().map(|f| [f[0], f[1], f[2], f[3]]).collect();
+        let patch_uvs = patch_mesh.uvs.as_ref().map(|uvs| uvs.iter().map(|c| [c[0], c[1], c[2], c[3]]).collect());
+        let patch_normals = patch_mesh.normals.as_ref().map(|normals| normals.iter().map(|c| [c[0], c[1], c[2], c[3]]).collect());
+
+        let triangle_boxes = triangles.iter().map(|tri| {
+            let [a, b, c] = tri.map(|i| positions[i]);
+            Aabb::surrounding(&Aabb::from_points(&a, &b), &Aabb::from_points(&a, &c)).pad_to_minimums()
+        });
+        let patch_boxes = patches.iter().map(|quad| patch_bounds(&quad.map(|i| positions[i])));
+        let boxes: Vec<Aabb> = triangle_boxes.chain(patch_boxes).collect();
+
+        Self { positions, triangles, uvs, normals, patches, patch_uvs, patch_normals, bvh: Bvh::new(&boxes), mat }
+    }
+
+    pub fn from_obj(path: impl AsRef<Path>, mat: Rc<dyn Material>) -> std::io::Result<Self> {
+        Ok(Self::new(&Mesh::load_obj(path)?, mat))
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn patch_count(&self) -> usize {
+        self.patches.len()
+    }
+
+    fn fill_patch(&self, ray: &Ray, index: usize, t: f64, u: f64, v: f64, hit_record: &mut HitRecord) {
+        let corners = self.patches[index].map(|i| self.positions[i]);
+        fill_patch_hit(ray, &corners, t, u, v, hit_record);
+
+        let weights = [(1.0 - u) * (1.0 - v), u * (1.0 - v), u * v, (1.0 - u) * v];
+        if let Some(normals) = &self.patch_normals {
+            let smooth_normal = (0..4).fold(Vec3::origin(), |sum, k| sum + weights[k] * normals[index][k]).normalize();
+            hit_record.normal = if hit_record.front_face { smooth_normal } else { -smooth_normal };
+        }
+        if let Some(uvs) = &self.patch_uvs {
+            let uv = uvs[index];
+            hit_record.u = (0..4).map(|k| weights[k] * uv[k][0]).sum();
+            hit_record.v = (0..4).map(|k| weights[k] * uv[k][1]).sum();
+
+            // the patch parameters (s, t) are not the texture's uv, so the derivatives go through the inverse
+            // of the uv map's jacobian: dp/du = (dp/ds dv/dt - dp/dt dv/ds) / det, dp/dv = (dp/dt du/ds - dp/ds du/dt) / det
+            let [q00, q10, q11, q01] = self.patches[index].map(|i| self.positions[i]);
+            let dpds = (1.0 - v) * (q10 - q00) + v * (q11 - q01);
+            let dpdt = (1.0 - u) * (q01 - q00) + u * (q11 - q10);
+            let duv_ds = [0, 1].map(|c| (1.0 - v) * (uv[1][c] - uv[0][c]) + v * (uv[2][c] - uv[3][c]));
+            let duv_dt = [0, 1].map(|c| (1.0 - u) * (uv[3][c] - uv[0][c]) + u * (uv[2][c] - uv[1][c]));
+            let determinant = duv_ds[0] * duv_dt[1] - duv_ds[1] * duv_dt[0];
+            (hit_record.tangent, hit_record.bitangent) = if determinant.abs() < 1e-12 {
+                (Vec3::origin(), Vec3::origin())
+            } else {
+                ((duv_dt[1] * dpds - duv_ds[1] * dpdt) / determinant, (duv_ds[0] * dpdt - duv_dt[0] * dpds) / determinant)
+            };
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut closest = None;
+
+        let triangle_count = self.triangles.len();
+
+        self.bvh.hit(ray, interval, |index, ray_t| {
+            if index >= triangle_count {
+                let corners = self.patches[index - triangle_count].map(|i| self.positions[i]);
+                let (t, u, v) = intersect_patch(ray, &corners, ray_t)?;
+                closest = Some((index, t, u, v));
+                return Some(t);
+            }
+
+            let [p0, p1, p2] = self.triangles[index].map(|i| &self.positions[i]);
+            let (t, b1, b2) = intersect_triangle(ray, p0, p1, p2)?;
+            if !ray_t.surrounds(t) {
+                return None;
+            }
+            closest = Some((index, t, b1, b2));
+            Some(t)
+        });
+
+        let Some((index, t, b1, b2)) = closest else {
+            return false;
+        };
+
+        hit_record.index = index;
+        hit_record.mat = Rc::clone(&self.mat);
+        if index >= triangle_count {
+            self.fill_patch(ray, index - triangle_count, t, b1, b2, hit_record);
+            return true;
+        }
+
+        let [p0, p1, p2] = self.triangles[index].map(|i| self.positions[i]);
+        let b0 = 1.0 - b1 - b2;
+
+        // the point from the barycentric weights comes with a known error bound, ray.at(t) does not
+        let (w0, w1, w2) = (b0 * p0, b1 * p1, b2 * p2);
+        hit_record.t = t;
+        hit_record.point = w0 + w1 + w2;
+        hit_record.error = gamma(7) * (w0.abs() + w1.abs() + w2.abs());
+
+        // facing comes from the flat triangle so both sides of a thin sheet work, shading from the corner normals
+        let geometric_normal = (p1 - p0).cross(&(p2 - p0)).normalize();
+        hit_record.set_face_normal(ray, &geometric_normal);
+        if let Some(normals) = &self.normals {
+            let [na, nb, nc] = normals[index];
+            let smooth_normal = (b0 * na + b1 * nb + b2 * nc).normalize();
+            hit_record.normal = if hit_record.front_face { smooth_normal } else { -smooth_normal };
+        }
+
+        // without uvs the barycentric weights stand in, which is what the default corner uvs give
+        let [ta, tb, tc] = self.uvs.as_ref().map_or([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]], |uvs| uvs[index]);
+        hit_record.u = b0 * ta[0] + b1 * tb[0] + b2 * tc[0];
+        hit_record.v = b0 * ta[1] + b1 * tb[1] + b2 * tc[1];
+        (hit_record.tangent, hit_record.bitangent) = triangle_derivatives(&[p0, p1, p2], &[ta, tb, tc]);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+// dp/du and dp/dv of the plane through a triangle, from its corner positions and uvs (pbrt's way),
+// both zero when the uvs are degenerate and say nothing about direction
+pub(crate) fn triangle_derivatives(p: &[Vec3<f64>; 3], uv: &[[f64; 2]; 3]) -> (Vec3<f64>, Vec3<f64>) {
+    let duv02 = [uv[0][0] - uv[2][0], uv[0][1] - uv[2][1]];
+    let duv12 = [uv[1][0] - uv[2][0], uv[1][1] - uv[2][1]];
+    let (dp02, dp12) = (p[0] - p[2], p[1] - p[2]);
+
+    let determinant = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+    if determinant.abs() < 1e-12 {
+        return (Vec3::origin(), Vec3::origin());
+    }
+    let dpdu = (duv12[1] * dp02 - duv02[1] * dp12) / determinant;
+    let dpdv = (duv02[0] * dp12 - duv12[0] * dp02) / determinant;
+    (dpdu, dpdv)
+}
+
+// moller-trumbore, returns t and the barycentric weights of p1 and p2
+pub(crate) fn intersect_triangle(ray: &Ray, p0: &Vec3<f64>, p1: &Vec3<f64>, p2: &Vec3<f64>) -> Option<(f64, f64, f64)> {
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+    let pvec = ray.direction().cross(&e2);
+    let det = e1.dot(&pvec);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin() - p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&e1);
+    let b2 = ray.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    Some((e2.dot(&qvec) * inv_det, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obj_faces_with_uvs_and_normals() {
+        let mesh = Mesh::parse_obj("
+            # a unit quad
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 2
+            o ignored
+            f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+        ").unwrap();
+
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2, 3]]);
+        assert_eq!(mesh.uvs, Some(vec![vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]]));
+        assert_eq!(mesh.normals, Some(vec![vec![Vec3::new(0.0, 0.0, 1.0); 4]]));
+
+        let triangles = mesh.triangulate();
+        assert_eq!(triangles.faces, vec![vec![0, 1, 2], vec![0, 2, 3]]);
+        assert_eq!(triangles.uvs.unwrap()[1], vec![[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    }
+
+    #[test]
+    fn obj_uvs_are_dropped_unless_every_face_has_them() {
+        let mesh = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\nf 2//1 4//1 3//1\n").unwrap();
+        assert!(mesh.uvs.is_none());
+        assert!(mesh.normals.is_some());
+    }
+
+    #[test]
+    fn triangle_mesh_from_obj_with_uvs_and_normals() {
+        let path = std::env::temp_dir().join(format!("tracer-mesh-test-{}.obj", std::process::id()));
+        fs::write(&path, "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\n").unwrap();
+        let mesh = TriangleMesh::from_obj(&path, Rc::new(crate::material::DefaultMaterial {}));
+        fs::remove_file(&path).unwrap();
+
+        let mesh = mesh.unwrap();
+        assert_eq!((mesh.triangle_count(), mesh.patch_count()), (2, 0));
+
+        let ray = Ray::new(Vec3::new(0.5, -0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut hit_record = HitRecord::default();
+        assert!(mesh.hit(&ray, &Interval::new(0.0, 10.0), &mut hit_record));
+        assert!((hit_record.t - 1.0).abs() < 1e-9);
+        assert!((hit_record.u - 0.75).abs() < 1e-9 && (hit_record.v - 0.25).abs() < 1e-9);
+        assert_eq!(hit_record.index, 0);
+        assert!((hit_record.normal - Vec3::new(0.0, 0.0, 1.0)).mag() < 1e-9);
+    }
+
+    #[test]
+    fn malformed_obj_is_an_error() {
+        for text in [
+            "v 0 0",
+            "v 0 0 zero",
+            "vt 0",
+            "v 0 0 0\nv 1 0 0\nf 1 2",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 /",
+        ] {
+            assert!(Mesh::parse_obj(text).is_err(), "{text:?} should not parse");
+        }
+    }
+}
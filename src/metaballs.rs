@@ -0,0 +1,329 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// one source of the field: weight * (1 - r^2 / radius^2)^3 inside radius, nothing outside (wyvill's kernel)
+#[derive(Debug, Clone, Copy)]
+pub struct Blob {
+    pub center: Vec3<f64>,
+    pub radius: f64,
+    pub weight: f64,
+}
+
+impl Blob {
+    pub fn new(center: Vec3<f64>, radius: f64, weight: f64) -> Self {
+        Self { center, radius, weight }
+    }
+
+    fn field(&self, p: &Vec3<f64>) -> f64 {
+        let q = 1.0 - (p - &self.center).dot(&(p - &self.center)) / (self.radius * self.radius);
+        if q > 0.0 { self.weight * q * q * q } else { 0.0 }
+    }
+
+    fn gradient(&self, p: &Vec3<f64>) -> Vec3<f64> {
+        let offset = p - &self.center;
+        let r2 = self.radius * self.radius;
+        let q = 1.0 - offset.dot(&offset) / r2;
+        if q > 0.0 { (-6.0 * self.weight * q * q / r2) * offset } else { Vec3::origin() }
+    }
+
+    // the kernel along a ray as a polynomial in s, where the ray is at origin + s * direction
+    // the squared distance is a quadratic in s, so the cubed kernel has degree 6
+    fn polynomial(&self, origin: &Vec3<f64>, direction: &Vec3<f64>) -> [f64; 7] {
+        let oc = origin - &self.center;
+        let r2 = self.radius * self.radius;
+        let q = [1.0 - oc.dot(&oc) / r2, -2.0 * oc.dot(direction) / r2, -direction.dot(direction) / r2];
+
+        let q2 = multiply(&q, &q);
+        let q3 = multiply(&q2, &q);
+        let mut coeffs = [0.0; 7];
+        for (c, q3) in coeffs.iter_mut().zip(q3.iter()) {
+            *c = self.weight * q3;
+        }
+        coeffs
+    }
+}
+
+fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    product
+}
+
+// blobby implicit surface: the set where the summed field of all blobs equals threshold
+// blobs whose spheres of influence touch are grouped into clusters with their own box, a ray only
+// looks at the blobs of the clusters it passes through
+// along the ray the field is a piecewise degree 6 polynomial, broken where a blob's sphere starts or ends;
+// each piece is searched for its first root by bisecting with interval bounds on the polynomial
+pub struct Metaballs {
+    blobs: Vec<Blob>,
+    clusters: Vec<(Aabb, Vec<usize>)>,
+    threshold: f64,
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Metaballs {
+    const MAX_DEPTH: u32 = 60;
+
+    pub fn new(blobs: Vec<Blob>, threshold: f64, mat: Rc<dyn Material>) -> Self {
+        assert!(threshold > 0.0, "the threshold has to be positive, the field is zero far away");
+
+        // union find over overlapping spheres of influence
+        let mut parent: Vec<usize> = (0..blobs.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for i in 0..blobs.len() {
+            for j in i + 1..blobs.len() {
+                if (blobs[i].center - blobs[j].center).mag() < blobs[i].radius + blobs[j].radius {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[a] = b;
+                }
+            }
+        }
+
+        let mut clusters: Vec<(Aabb, Vec<usize>)> = Vec::new();
+        let mut cluster_of = vec![usize::MAX; blobs.len()];
+        for (i, blob) in blobs.iter().enumerate() {
+            let r = root(&mut parent, i);
+            if cluster_of[r] == usize::MAX {
+                cluster_of[r] = clusters.len();
+                clusters.push((Aabb::empty(), Vec::new()));
+            }
+            let (bbox, members) = &mut clusters[cluster_of[r]];
+            let extent = Vec3::new(blob.radius, blob.radius, blob.radius);
+            *bbox = Aabb::surrounding(bbox, &Aabb::from_points(&(blob.center - extent), &(blob.center + extent)));
+            members.push(i);
+        }
+
+        let bbox = clusters.iter().fold(Aabb::empty(), |bbox, (cluster_box, _)| Aabb::surrounding(&bbox, cluster_box));
+        Self { blobs, clusters, threshold, mat, bbox }
+    }
+
+    pub fn field(&self, p: &Vec3<f64>) -> f64 {
+        self.blobs.iter().map(|blob| blob.field(p)).sum()
+    }
+
+    fn gradient(&self, p: &Vec3<f64>) -> Vec3<f64> {
+        self.blobs.iter().fold(Vec3::origin(), |sum, blob| sum + blob.gradient(p))
+    }
+
+    // first t inside interval where the blobs of one cluster reach the threshold
+    fn hit_cluster(&self, members: &[usize], ray: &Ray, interval: &Interval) -> Option<f64> {
+        // where the ray is inside each blob's sphere of influence
+        let mut spans = Vec::new();
+        let mut breaks = Vec::new();
+        for &i in members {
+            let blob = &self.blobs[i];
+            let oc = ray.origin() - &blob.center;
+            let a = ray.direction().dot(ray.direction());
+            let h = ray.direction().dot(&oc);
+            let c = oc.dot(&oc) - blob.radius * blob.radius;
+            let discriminant = h * h - a * c;
+            if discriminant <= 0.0 {
+                continue;
+            }
+            let root = discriminant.sqrt();
+            let (t0, t1) = ((-h - root) / a, (-h + root) / a);
+            if t1 <= interval.min() || t0 >= interval.max() {
+                continue;
+            }
+            spans.push((i, t0, t1));
+            breaks.push(t0.max(interval.min()));
+            breaks.push(t1.min(interval.max()));
+        }
+        breaks.sort_by(f64::total_cmp);
+        breaks.dedup();
+
+        for piece in breaks.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let middle = 0.5 * (start + end);
+
+            // the field on this piece, in s = t - start so the coefficients stay well scaled
+            let origin = ray.at(start);
+            let mut coeffs = [0.0; 7];
+            for &(i, t0, t1) in spans.iter() {
+                if t0 < middle && middle < t1 {
+                    for (c, b) in coeffs.iter_mut().zip(self.blobs[i].polynomial(&origin, ray.direction()).iter()) {
+                        *c += b;
+                    }
+                }
+            }
+            coeffs[0] -= self.threshold;
+
+            if let Some(s) = first_root(&coeffs, &Interval::new(0.0, end - start), Self::MAX_DEPTH) {
+                let t = start + s;
+                if interval.surrounds(t) {
+                    return Some(t);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Hittable for Metaballs {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut closest = interval.max();
+        let mut found = false;
+
+        for (bbox, members) in self.clusters.iter() {
+            let mut ray_t = Interval::new(interval.min(), closest);
+            if !bbox.hit(ray, &mut ray_t) {
+                continue;
+            }
+            if let Some(t) = self.hit_cluster(members, ray, &Interval::new(interval.min(), closest)) {
+                closest = t;
+                found = true;
+            }
+        }
+
+        if !found {
+            return false;
+        }
+
+        hit_record.t = closest;
+        hit_record.point = ray.at(closest);
+
+        // bisection stops within a relative 1e-12 of the root
+        let tolerance = 1e-12 * closest.abs().max(1.0) * ray.direction().mag();
+        hit_record.error = Vec3::new(tolerance, tolerance, tolerance) + gamma(3) * hit_record.point.abs();
+
+        // the field falls off outwards, so the outward normal points down its gradient
+        let outward_normal = -self.gradient(&hit_record.point).normalize();
+        hit_record.set_face_normal(ray, &outward_normal);
+
+        // no uv here, give anisotropic materials a tangent running around the y axis as on a sphere
+        hit_record.tangent = Vec3::new(*outward_normal.z(), 0.0, -outward_normal.x());
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+fn evaluate(coeffs: &[f64], s: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |sum, c| sum * s + c)
+}
+
+// horner's rule in interval arithmetic, encloses every value of the polynomial over range
+fn bound(coeffs: &[f64], range: &Interval) -> Interval {
+    coeffs.iter().rev().fold(Interval::new(0.0, 0.0), |sum, &c| {
+        let products = [sum.min() * range.min(), sum.min() * range.max(), sum.max() * range.min(), sum.max() * range.max()];
+        let low = products.iter().cloned().fold(f64::INFINITY, f64::min);
+        let high = products.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        Interval::new(low + c, high + c)
+    })
+}
+
+// smallest root of the polynomial inside range: halves the range, always trying the near half first,
+// and drops every part where the interval bound shows the polynomial cannot reach zero
+fn first_root(coeffs: &[f64], range: &Interval, depth: u32) -> Option<f64> {
+    if !bound(coeffs, range).contains(0.0) {
+        return None;
+    }
+
+    let (low, high) = (range.min(), range.max());
+    if depth == 0 || high - low <= 1e-12 * high.abs().max(1.0) {
+        // only report a real crossing, not a near miss that the bound could not rule out
+        let crosses = evaluate(coeffs, low).signum() != evaluate(coeffs, high).signum();
+        return if crosses { Some(0.5 * (low + high)) } else { None };
+    }
+
+    let middle = 0.5 * (low + high);
+    first_root(coeffs, &Interval::new(low, middle), depth - 1).or_else(|| first_root(coeffs, &Interval::new(middle, high), depth - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn metaballs(blobs: Vec<Blob>, threshold: f64) -> Metaballs {
+        Metaballs::new(blobs, threshold, Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))))
+    }
+
+    #[test]
+    fn first_root_finds_the_nearer_crossing() {
+        // (s - 0.3)(s - 0.7)
+        let root = first_root(&[0.21, -1.0, 1.0], &Interval::new(0.0, 1.0), Metaballs::MAX_DEPTH).unwrap();
+        assert!((root - 0.3).abs() < 1e-10);
+
+        // (s - 0.5)^2 + 1e-9 comes close to zero without crossing it
+        assert!(first_root(&[0.250000001, -1.0, 1.0], &Interval::new(0.0, 1.0), Metaballs::MAX_DEPTH).is_none());
+    }
+
+    #[test]
+    fn a_single_blob_is_hit_at_its_iso_radius() {
+        let (radius, threshold) = (2.0, 0.5);
+        let balls = metaballs(vec![Blob::new(Vec3::new(0.0, 0.0, 0.0), radius, 1.0)], threshold);
+
+        // (1 - r^2 / radius^2)^3 = threshold
+        let iso_radius = radius * (1.0 - f64::cbrt(threshold)).sqrt();
+
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut hit = HitRecord::default();
+        assert!(balls.hit(&ray, &Interval::new(0.0, f64::INFINITY), &mut hit));
+        assert!((hit.t - (5.0 - iso_radius)).abs() < 1e-9, "hit at {} instead of {}", hit.t, 5.0 - iso_radius);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).mag() < 1e-9);
+
+        // and from inside, on the way out
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(balls.hit(&ray, &Interval::new(0.0, f64::INFINITY), &mut hit));
+        assert!((hit.t - iso_radius).abs() < 1e-9);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn cluster_boxes_hold_the_merged_surface() {
+        let balls = metaballs(vec![Blob::new(Vec3::new(-0.6, 0.0, 0.0), 1.0, 1.0), Blob::new(Vec3::new(0.6, 0.2, 0.0), 1.0, 1.0)], 0.3);
+        assert_eq!(balls.clusters.len(), 1);
+        let bbox = &balls.clusters[0].0;
+
+        // a grid of rays along each axis from outside, every surface point found has to lie in the cluster's box
+        let mut hits = 0;
+        for axis in 0..3 {
+            for a in -12..=12 {
+                for b in -12..=12 {
+                    let (a, b) = (0.1 * a as f64, 0.1 * b as f64);
+                    let (origin, direction) = match axis {
+                        0 => (Vec3::new(-5.0, a, b), Vec3::new(1.0, 0.0, 0.0)),
+                        1 => (Vec3::new(a, -5.0, b), Vec3::new(0.0, 1.0, 0.0)),
+                        _ => (Vec3::new(a, b, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+                    };
+                    let mut hit = HitRecord::default();
+                    if !balls.hit(&Ray::new(origin, direction), &Interval::new(0.0, f64::INFINITY), &mut hit) {
+                        continue;
+                    }
+                    hits += 1;
+                    assert!((balls.field(&hit.point) - 0.3).abs() < 1e-6);
+                    for axis in 0..3 {
+                        assert!(bbox.axis_interval(axis).contains(hit.point.axis(axis)), "{:?} is outside the cluster box", hit.point);
+                    }
+                }
+            }
+        }
+
+        // the two blobs merged: the surface spans the gap between them
+        assert!(hits > 100);
+        let ray = Ray::new(Vec3::new(0.0, 0.1, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(balls.hit(&ray, &Interval::new(0.0, f64::INFINITY), &mut HitRecord::default()));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::core::{Vec3, PI};
+
+// shared microfacet maths for the rough materials, all in a local shading frame where the normal is +z
+// and x runs along the surface tangent
+
+// trowbridge-reitz (ggx) distribution of microfacet normals, alpha_x and alpha_y are the roughness
+// along the tangent and the bitangent (equal for an isotropic surface)
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    // below this the distribution is a spike that floating point cannot sample sensibly
+    const MIN_ALPHA: f64 = 1e-4;
+
+    // perceptual roughness in [0, 1], squared into alpha so the look changes evenly along the range
+    pub(crate) fn new(roughness_x: f64, roughness_y: f64) -> Self {
+        let alpha = |r: f64| (r.clamp(0.0, 1.0) * r.clamp(0.0, 1.0)).max(Self::MIN_ALPHA);
+        Self { alpha_x: alpha(roughness_x), alpha_y: alpha(roughness_y) }
+    }
+
+    // density of microfacet normal m, per unit projected area
+    pub(crate) fn d(&self, m: &Vec3<f64>) -> f64 {
+        if *m.z() <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = (m.x() / self.alpha_x, m.y() / self.alpha_y);
+        let e = x * x + y * y + m.z() * m.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // smith's lambda: how much of the surface seen from w is hidden by other microfacets
+    fn lambda(&self, w: &Vec3<f64>) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let a2 = self.alpha_x * self.alpha_x * w.x() * w.x() + self.alpha_y * self.alpha_y * w.y() * w.y();
+        0.5 * ((1.0 + a2 / z2).sqrt() - 1.0)
+    }
+
+    // masking from one direction
+    pub(crate) fn g1(&self, w: &Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated masking and shadowing for a pair of directions
+    pub(crate) fn g(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of the normals visible from wo, what sample_visible draws m from
+    pub(crate) fn visible_pdf(&self, wo: &Vec3<f64>, m: &Vec3<f64>) -> f64 {
+        if *wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z()
+    }
+
+    // a microfacet normal drawn in proportion to how much of it wo sees (heitz 2018), u1 and u2 in [0, 1)
+    // wo has to be above the surface
+    pub(crate) fn sample_visible(&self, wo: &Vec3<f64>, u1: f64, u2: f64) -> Vec3<f64> {
+        // stretch the view so the distribution becomes the hemisphere of a unit roughness surface
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), *wo.z()).normalize();
+
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 { Vec3::new(-vh.y(), *vh.x(), 0.0) / len2.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(&t1);
+
+        // uniform point on a disk, squashed onto the visible half of it
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // and back to the real roughness
+        Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(0.0)).normalize()
+    }
+}
+
+// burley's gtr1 (berry) distribution, a long tailed lobe for clear coats; takes the cosine of m to the normal
+pub(crate) fn gtr1(cos_m: f64, alpha: f64) -> f64 {
+    if cos_m <= 0.0 {
+        return 0.0;
+    }
+    if alpha >= 1.0 {
+        return 1.0 / PI;
+    }
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_m * cos_m))
+}
+
+// microfacet normal with density gtr1(m.z) * m.z, u1 and u2 in [0, 1]
+pub(crate) fn sample_gtr1(alpha: f64, u1: f64, u2: f64) -> Vec3<f64> {
+    let a2 = alpha * alpha;
+    let cos2 = if alpha >= 1.0 { 1.0 - u1 } else { (1.0 - a2.powf(1.0 - u1)) / (1.0 - a2) };
+    let cos_m = cos2.clamp(0.0, 1.0).sqrt();
+    let sin_m = (1.0 - cos_m * cos_m).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3::new(sin_m * phi.cos(), sin_m * phi.sin(), cos_m)
+}
+
+// schlick's (1 - cos)^5 fresnel weight
+pub(crate) fn schlick_weight(cos_theta: f64) -> f64 {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0);
+    m * m * m * m * m
+}
+
+// mirror w about m, both pointing away from the surface
+pub(crate) fn reflect(w: &Vec3<f64>, m: &Vec3<f64>) -> Vec3<f64> {
+    2.0 * w.dot(m) * *m - *w
+}
+
+// w bent through microfacet m into the other side, eta is the index on the far side over the one on w's side
+// returns None on total internal reflection
+pub(crate) fn refract(w: &Vec3<f64>, m: &Vec3<f64>, eta: f64) -> Option<Vec3<f64>> {
+    let cos_i = w.dot(m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-*w / eta + (cos_i / eta - cos_t) * *m)
+}
+
+// unpolarised fresnel reflectance at a boundary between dielectrics, eta as in refract
+pub(crate) fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// unpolarised fresnel reflectance of a conductor with complex index eta + i k, for one wavelength
+fn fresnel_conductor_channel(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rs + rp)
+}
+
+// fresnel reflectance of a conductor per rgb channel
+pub(crate) fn fresnel_conductor(cos_theta: f64, eta: &Vec3<f64>, k: &Vec3<f64>) -> Vec3<f64> {
+    let cos_theta = cos_theta.clamp(0.0, 1.0);
+    Vec3::new(
+        fresnel_conductor_channel(cos_theta, *eta.x(), *k.x()),
+        fresnel_conductor_channel(cos_theta, *eta.y(), *k.y()),
+        fresnel_conductor_channel(cos_theta, *eta.z(), *k.z()),
+    )
+}
@@ -0,0 +1,63 @@
+use crate::core::Vec3;
+
+// orthonormal basis: a right handed frame (u, v, w) built around a given w direction
+// used to place primitives along an arbitrary axis and to work in a surface's local frame
+#[derive(Clone, Copy)]
+pub struct Onb {
+    u: Vec3<f64>,
+    v: Vec3<f64>,
+    w: Vec3<f64>,
+}
+
+impl Onb {
+    pub fn new(n: &Vec3<f64>) -> Self {
+        let w = n.normalize();
+
+        // any vector not parallel to w will do as a helper
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+
+        let v = w.cross(&a).normalize();
+        let u = v.cross(&w);
+
+        Self { u, v, w }
+    }
+
+    // frame around n with u along the part of tangent that lies in the surface,
+    // falls back to an arbitrary u when the tangent is missing, not finite or (nearly) parallel to n
+    pub fn with_tangent(n: &Vec3<f64>, tangent: &Vec3<f64>) -> Self {
+        let w = n.normalize();
+        let u = *tangent - tangent.dot(&w) * w;
+        if u.mag().is_nan() || u.mag() <= 1e-6 * tangent.mag() {
+            return Self::new(n);
+        }
+
+        let u = u.normalize();
+        Self { u, v: w.cross(&u), w }
+    }
+
+    pub fn u(&self) -> &Vec3<f64> {
+        &self.u
+    }
+
+    pub fn v(&self) -> &Vec3<f64> {
+        &self.v
+    }
+
+    pub fn w(&self) -> &Vec3<f64> {
+        &self.w
+    }
+
+    // local (u, v, w) coordinates to world space
+    pub fn transform(&self, a: &Vec3<f64>) -> Vec3<f64> {
+        (*a.x() * self.u) + (*a.y() * self.v) + (*a.z() * self.w)
+    }
+
+    // world space to local (u, v, w) coordinates
+    pub fn to_local(&self, a: &Vec3<f64>) -> Vec3<f64> {
+        Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
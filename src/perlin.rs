@@ -0,0 +1,124 @@
+use crate::core::Vec3;
+use crate::seeded_rng;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// perlin gradient noise: a random unit gradient at every lattice point, picked by hashing the
+// lattice coordinates through three permutation tables; the same seed always builds the same tables
+pub struct Perlin {
+    gradients: Vec<Vec3<f64>>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    const POINT_COUNT: usize = 256;
+
+    pub fn new(seed: u64) -> Self {
+        let mut rng = seeded_rng(seed);
+
+        let gradients = (0..Self::POINT_COUNT)
+            .map(|_| loop {
+                let p = Vec3::new(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0));
+                let len = p.mag();
+                if len > 1e-3 && len <= 1.0 {
+                    break p / len;
+                }
+            })
+            .collect();
+
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..Self::POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+
+        Self { gradients, perm_x, perm_y, perm_z }
+    }
+
+    // smooth noise in about [-1, 1], zero on every lattice point
+    pub fn noise(&self, p: &Vec3<f64>) -> f64 {
+        let (x, y, z) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - x, p.y() - y, p.z() - z);
+        let (i, j, k) = (x as i64, y as i64, z as i64);
+
+        // hermite smoothing hides the lattice
+        let (uu, vv, ww) = (u * u * (3.0 - 2.0 * u), v * v * (3.0 - 2.0 * v), w * w * (3.0 - 2.0 * w));
+        let mask = Self::POINT_COUNT as i64 - 1;
+
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & mask) as usize]
+                        ^ self.perm_y[((j + dj) & mask) as usize]
+                        ^ self.perm_z[((k + dk) & mask) as usize];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let offset = Vec3::new(u - fi, v - fj, w - fk);
+
+                    let weight = (fi * uu + (1.0 - fi) * (1.0 - uu)) * (fj * vv + (1.0 - fj) * (1.0 - vv)) * (fk * ww + (1.0 - fk) * (1.0 - ww));
+                    sum += weight * self.gradients[index].dot(&offset);
+                }
+            }
+        }
+        sum
+    }
+
+    // fractal brownian motion: octaves of noise, each twice the frequency and half the amplitude of the last
+    pub fn fbm(&self, p: &Vec3<f64>, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut point = *p;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&point);
+            amplitude *= 0.5;
+            point = 2.0 * point;
+        }
+        sum
+    }
+
+    // like fbm but summing the absolute value, which gives the creased look of turbulent flow
+    pub fn turbulence(&self, p: &Vec3<f64>, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut point = *p;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&point).abs();
+            amplitude *= 0.5;
+            point = 2.0 * point;
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<Vec3<f64>> {
+        (0..50).map(|i| Vec3::new(0.37 * i as f64, 1.3 - 0.11 * i as f64, 0.73 * (i % 7) as f64 + 0.5)).collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_noise() {
+        // two separately built tables, as two renders would build them
+        let (a, b) = (Perlin::new(7), Perlin::new(7));
+        for p in points() {
+            assert_eq!(a.noise(&p), b.noise(&p));
+            assert_eq!(a.fbm(&p, 5), b.fbm(&p, 5));
+            assert_eq!(a.turbulence(&p, 5), b.turbulence(&p, 5));
+        }
+    }
+
+    #[test]
+    fn another_seed_gives_other_noise() {
+        let (a, b) = (Perlin::new(7), Perlin::new(8));
+        let differs = |f: &dyn Fn(&Perlin, &Vec3<f64>) -> f64| points().iter().any(|p| f(&a, p) != f(&b, p));
+        assert!(differs(&|perlin, p| perlin.noise(p)));
+        assert!(differs(&|perlin, p| perlin.fbm(p, 5)));
+        assert!(differs(&|perlin, p| perlin.turbulence(p, 5)));
+    }
+}
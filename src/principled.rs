@@ -0,0 +1,325 @@
+use crate::core::{Vec3, PI};
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet::{fresnel_dielectric, gtr1, reflect, refract, sample_gtr1, schlick_weight, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::random_canonical;
+use crate::texture::{SolidColor, Texture};
+
+use std::rc::Rc;
+
+// disney's principled bsdf (burley 2012, with the 2015 transmission): one material whose sliders blend
+// a diffuse + sheen lobe, a ggx specular lobe, a glass lobe and a gtr1 clear coat
+// every parameter is a texture, scalar ones read Texture::scalar so a grey map drives them directly
+// sampling picks one lobe by how much it is expected to reflect, then weighs the bounce against the
+// combined density of all the lobes (one sample multiple importance sampling)
+pub struct Principled
+{
+    base_color: Rc<dyn Texture>,
+    metallic: Rc<dyn Texture>,
+    roughness: Rc<dyn Texture>,
+    specular: Rc<dyn Texture>,
+    specular_tint: Rc<dyn Texture>,
+    sheen: Rc<dyn Texture>,
+    clearcoat: Rc<dyn Texture>,
+    clearcoat_gloss: Rc<dyn Texture>,
+    transmission: Rc<dyn Texture>,
+    ior: Rc<dyn Texture>,
+}
+
+fn constant(value: f64) -> Rc<dyn Texture>
+{
+    Rc::new(SolidColor::from_rgb(value, value, value))
+}
+
+impl Principled
+{
+    pub fn new(base_color: Vec3<f64>) -> Self
+    {
+        Self::with_texture(Rc::new(SolidColor::new(base_color)))
+    }
+
+    // a rough dielectric with disney's defaults, change them with the with_* setters
+    pub fn with_texture(base_color: Rc<dyn Texture>) -> Self
+    {
+        Self{
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            ior: constant(1.5),
+        }
+    }
+
+    pub fn with_metallic(self, metallic: f64) -> Self { self.with_metallic_texture(constant(metallic)) }
+    pub fn with_metallic_texture(self, metallic: Rc<dyn Texture>) -> Self { Self{metallic, ..self} }
+
+    pub fn with_roughness(self, roughness: f64) -> Self { self.with_roughness_texture(constant(roughness)) }
+    pub fn with_roughness_texture(self, roughness: Rc<dyn Texture>) -> Self { Self{roughness, ..self} }
+
+    // 0.5 is a 4% reflectance at normal incidence, the usual for dielectrics
+    pub fn with_specular(self, specular: f64) -> Self { self.with_specular_texture(constant(specular)) }
+    pub fn with_specular_texture(self, specular: Rc<dyn Texture>) -> Self { Self{specular, ..self} }
+
+    // how much the dielectric highlight takes on the hue of the base colour
+    pub fn with_specular_tint(self, specular_tint: f64) -> Self { self.with_specular_tint_texture(constant(specular_tint)) }
+    pub fn with_specular_tint_texture(self, specular_tint: Rc<dyn Texture>) -> Self { Self{specular_tint, ..self} }
+
+    // soft grazing angle rim, for cloth
+    pub fn with_sheen(self, sheen: f64) -> Self { self.with_sheen_texture(constant(sheen)) }
+    pub fn with_sheen_texture(self, sheen: Rc<dyn Texture>) -> Self { Self{sheen, ..self} }
+
+    pub fn with_clearcoat(self, clearcoat: f64) -> Self { self.with_clearcoat_texture(constant(clearcoat)) }
+    pub fn with_clearcoat_texture(self, clearcoat: Rc<dyn Texture>) -> Self { Self{clearcoat, ..self} }
+
+    // 0 is a satin coat, 1 a glossy one
+    pub fn with_clearcoat_gloss(self, clearcoat_gloss: f64) -> Self { self.with_clearcoat_gloss_texture(constant(clearcoat_gloss)) }
+    pub fn with_clearcoat_gloss_texture(self, clearcoat_gloss: Rc<dyn Texture>) -> Self { Self{clearcoat_gloss, ..self} }
+
+    // share of the non metallic part that is glass instead of diffuse
+    pub fn with_transmission(self, transmission: f64) -> Self { self.with_transmission_texture(constant(transmission)) }
+    pub fn with_transmission_texture(self, transmission: Rc<dyn Texture>) -> Self { Self{transmission, ..self} }
+
+    pub fn with_ior(self, ior: f64) -> Self { self.with_ior_texture(constant(ior)) }
+    pub fn with_ior_texture(self, ior: Rc<dyn Texture>) -> Self { Self{ior, ..self} }
+
+    fn lobes(&self, hit_record: &HitRecord, cos_o: f64) -> Lobes
+    {
+        let scalar = |tex: &Rc<dyn Texture>| tex.scalar_at(hit_record).clamp(0.0, 1.0);
+
+        let base = self.base_color.value_at(hit_record);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let sheen = scalar(&self.sheen);
+        let clearcoat = scalar(&self.clearcoat);
+        let transmission = scalar(&self.transmission);
+
+        // the base colour with its brightness taken out, for the tints
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let lum = luminance(&base);
+        let tint = if lum > 0.0 { base / lum } else { white };
+        let specular_tint = scalar(&self.specular_tint);
+        let dielectric_spec = (0.08 * scalar(&self.specular)) * ((1.0 - specular_tint) * white + specular_tint * tint);
+
+        let weights = [
+            (1.0 - metallic) * (1.0 - transmission),
+            1.0 - (1.0 - metallic) * transmission,
+            (1.0 - metallic) * transmission,
+            0.25 * clearcoat,
+        ];
+        let spec0 = (1.0 - metallic) * dielectric_spec + metallic * base;
+
+        // pick lobes by a guess at how much light each sends back from this angle
+        let fresnel = |f0: &Vec3<f64>| luminance(&(*f0 + schlick_weight(cos_o) * (white - *f0)));
+        let expected = [
+            weights[0] * (lum + sheen),
+            weights[1] * fresnel(&spec0),
+            weights[2],
+            weights[3] * fresnel(&Vec3::new(0.04, 0.04, 0.04)),
+        ];
+        let total: f64 = expected.iter().sum();
+        let probabilities = expected.map(|e| if total > 0.0 { e / total } else { 0.0 });
+
+        let gloss = scalar(&self.clearcoat_gloss);
+        Lobes{
+            base,
+            roughness,
+            sheen_color: sheen * (0.5 * white + 0.5 * tint),
+            spec0,
+            eta: self.ior.scalar_at(hit_record).max(1.0),
+            ggx: Ggx::new(roughness, roughness),
+            coat_alpha: (1.0 - gloss) * 0.1 + gloss * 0.001,
+            coat_ggx: Ggx::new(0.5, 0.5),
+            weights,
+            probabilities,
+        }
+    }
+}
+
+fn luminance(c: &Vec3<f64>) -> f64
+{
+    0.3 * c.x() + 0.6 * c.y() + 0.1 * c.z()
+}
+
+// everything about the bsdf at one hit, in the local frame where the normal is +z
+struct Lobes
+{
+    base: Vec3<f64>,
+    roughness: f64,
+    sheen_color: Vec3<f64>,
+    spec0: Vec3<f64>,           // specular reflectance at normal incidence
+    eta: f64,                   // inside over outside
+    ggx: Ggx,
+    coat_alpha: f64,
+    coat_ggx: Ggx,
+    weights: [f64; 4],          // diffuse, specular, glass, clear coat
+    probabilities: [f64; 4],    // chance of sampling each lobe, same order
+}
+
+impl Lobes
+{
+    // bsdf times the cosine at wi, all lobes together
+    fn eval(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> Vec3<f64>
+    {
+        let mut f = self.weights[2] * self.glass(wo, wi);
+
+        if *wi.z() > 0.0
+        {
+            let h = (*wo + *wi).normalize();
+            let cos_d = wi.dot(&h);
+            let (cos_o, cos_i) = (*wo.z(), *wi.z());
+
+            // burley diffuse: darker at grazing angles when smooth, a retro reflective rim when rough
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let fl = 1.0 + (fd90 - 1.0) * schlick_weight(cos_i);
+            let fv = 1.0 + (fd90 - 1.0) * schlick_weight(cos_o);
+            let diffuse = (fl * fv / PI) * self.base + schlick_weight(cos_d) * self.sheen_color;
+
+            let fresnel = self.spec0 + schlick_weight(cos_d) * (Vec3::new(1.0, 1.0, 1.0) - self.spec0);
+            let specular = (self.ggx.d(&h) * self.ggx.g(wo, wi) / (4.0 * cos_o * cos_i)) * fresnel;
+
+            let coat_fresnel = 0.04 + 0.96 * schlick_weight(cos_d);
+            let coat = gtr1(*h.z(), self.coat_alpha) * coat_fresnel * self.coat_ggx.g(wo, wi) / (4.0 * cos_o * cos_i);
+
+            f += self.weights[0] * diffuse + self.weights[1] * specular + Vec3::new(1.0, 1.0, 1.0) * (self.weights[3] * coat);
+        }
+
+        f * wi.z().abs()
+    }
+
+    // glass bsdf (no cosine): rough reflection on this side, rough refraction tinted by the base colour to the other
+    fn glass(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> Vec3<f64>
+    {
+        if *wi.z() > 0.0
+        {
+            let h = (*wo + *wi).normalize();
+            let fresnel = fresnel_dielectric(wo.dot(&h), self.eta);
+            let f = fresnel * self.ggx.d(&h) * self.ggx.g(wo, wi) / (4.0 * wo.z() * wi.z());
+            return Vec3::new(f, f, f);
+        }
+
+        let Some((h, denom)) = self.refraction_half_vector(wo, wi) else {
+            return Vec3::origin();
+        };
+        let fresnel = fresnel_dielectric(wo.dot(&h), self.eta);
+        let f = (1.0 - fresnel) * self.ggx.d(&h) * self.ggx.g(wo, wi) * self.eta * self.eta * wi.dot(&h).abs() * wo.dot(&h)
+            / (wo.z() * wi.z().abs() * denom * denom);
+        f * self.base
+    }
+
+    // the microfacet normal that refracts wo into wi, and wo.h + eta wi.h, if there is one
+    fn refraction_half_vector(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> Option<(Vec3<f64>, f64)>
+    {
+        let h = *wo + self.eta * *wi;
+        if h.mag() == 0.0
+        {
+            return None;
+        }
+        let h = if *h.z() < 0.0 { -h.normalize() } else { h.normalize() };
+        let denom = wo.dot(&h) + self.eta * wi.dot(&h);
+        if wo.dot(&h) <= 0.0 || wi.dot(&h) >= 0.0 || denom == 0.0
+        {
+            return None;
+        }
+        Some((h, denom))
+    }
+
+    // density of wi over all the lobes, each weighted by its chance of being picked
+    fn pdf(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> f64
+    {
+        let [diffuse, specular, glass, coat] = self.probabilities;
+
+        if *wi.z() <= 0.0
+        {
+            let Some((h, denom)) = self.refraction_half_vector(wo, wi) else {
+                return 0.0;
+            };
+            let fresnel = fresnel_dielectric(wo.dot(&h), self.eta);
+            let jacobian = self.eta * self.eta * wi.dot(&h).abs() / (denom * denom);
+            return glass * (1.0 - fresnel) * self.ggx.visible_pdf(wo, &h) * jacobian;
+        }
+
+        let h = (*wo + *wi).normalize();
+        let reflection = self.ggx.visible_pdf(wo, &h) / (4.0 * wo.dot(&h));
+        let fresnel = fresnel_dielectric(wo.dot(&h), self.eta);
+        let coat_pdf = gtr1(*h.z(), self.coat_alpha) * h.z() / (4.0 * wo.dot(&h));
+
+        diffuse * wi.z() / PI + specular * reflection + glass * fresnel * reflection + coat * coat_pdf
+    }
+
+    // a direction from one lobe, None if that lobe sent it below the surface
+    fn sample(&self, wo: &Vec3<f64>) -> Option<Vec3<f64>>
+    {
+        // pick from [0, 1), and never past the last lobe with any weight in case the weights sum to a hair under one
+        let last = self.probabilities.iter().rposition(|&p| p > 0.0).unwrap_or(0);
+        let mut pick = random_canonical();
+        let mut lobe = 0;
+        while lobe < last && pick >= self.probabilities[lobe]
+        {
+            pick -= self.probabilities[lobe];
+            lobe += 1;
+        }
+
+        let wi = match lobe {
+            0 => Vec3::random_cosine_direction(),
+            1 => reflect(wo, &self.ggx.sample_visible(wo, random_canonical(), random_canonical())),
+            2 => {
+                let m = self.ggx.sample_visible(wo, random_canonical(), random_canonical());
+                match refract(wo, &m, self.eta) {
+                    Some(wt) if random_canonical() >= fresnel_dielectric(wo.dot(&m), self.eta) => wt,
+                    _ => reflect(wo, &m),
+                }
+            }
+            _ => reflect(wo, &sample_gtr1(self.coat_alpha, random_canonical(), random_canonical())),
+        };
+
+        // only the glass lobe may cross the surface
+        if *wi.z() == 0.0 || (*wi.z() < 0.0 && lobe != 2)
+        {
+            return None;
+        }
+        Some(wi)
+    }
+}
+
+impl Material for Principled
+{
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        let frame = Onb::with_tangent(&hit_record.normal, &hit_record.tangent);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if *wo.z() <= 0.0
+        {
+            return false;
+        }
+
+        let mut lobes = self.lobes(hit_record, *wo.z());
+
+        // from inside a transmissive object only the glass interface is left, seen from the dense side
+        if !hit_record.front_face && lobes.weights[2] > 0.0
+        {
+            lobes.eta = 1.0 / lobes.eta;
+            lobes.base = Vec3::new(1.0, 1.0, 1.0);
+            lobes.weights = [0.0, 0.0, 1.0, 0.0];
+            lobes.probabilities = [0.0, 0.0, 1.0, 0.0];
+        }
+
+        let Some(wi) = lobes.sample(&wo) else {
+            return false;
+        };
+        let pdf = lobes.pdf(&wo, &wi);
+        if pdf <= 0.0 || !pdf.is_finite()
+        {
+            return false;
+        }
+
+        *attenuation = lobes.eval(&wo, &wi) / pdf;
+        *scattered = hit_record.spawn_ray(frame.transform(&wi), ray_in.time());
+        true
+    }
+}
@@ -0,0 +1,79 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// flat parallelogram with corner q and edges u and v, the front side faces along u x v
+pub struct Quad {
+    q: Vec3<f64>,
+    u: Vec3<f64>,
+    v: Vec3<f64>,
+    w: Vec3<f64>,       // n / (n . n), turns a point on the plane into its (alpha, beta) coordinates
+    normal: Vec3<f64>,
+    d: f64,             // plane equation n . p = d
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Vec3<f64>, u: Vec3<f64>, v: Vec3<f64>, mat: Rc<dyn Material>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        let d = normal.dot(&q);
+        let w = n / n.dot(&n);
+
+        // box around both diagonals
+        let diagonal1 = Aabb::from_points(&q, &(q + u + v));
+        let diagonal2 = Aabb::from_points(&(q + u), &(q + v));
+        let bbox = Aabb::surrounding(&diagonal1, &diagonal2).pad_to_minimums();
+
+        Self { q, u, v, w, normal, d, mat, bbox }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(ray.direction());
+
+        // the ray runs parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin())) / denom;
+        if !interval.surrounds(t) {
+            return false;
+        }
+
+        // express the hit point in the quad's own coordinates
+        let intersection = ray.at(t);
+        let planar_hitpt = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        // rebuilt from the quad's own coordinates, which bounds its error
+        let (along_u, along_v) = (alpha * self.u, beta * self.v);
+        hit_record.t = t;
+        hit_record.point = self.q + along_u + along_v;
+        hit_record.error = gamma(7) * (self.q.abs() + along_u.abs() + along_v.abs());
+        hit_record.set_face_normal(ray, &self.normal);
+        hit_record.u = alpha;
+        hit_record.v = beta;
+        hit_record.tangent = self.u;
+        hit_record.bitangent = self.v;
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
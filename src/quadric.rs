@@ -0,0 +1,535 @@
+use crate::aabb::Aabb;
+use crate::core::{Vec3, PI};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::{degrees_to_radians, gamma, Material};
+
+use std::rc::Rc;
+
+// analytic quadric (and quartic) primitives: cylinder, cone, disk/annulus and torus
+// each one is intersected in its own local frame where the symmetry axis is z and phi = 0 lies on x;
+// phi_max cuts the surface down to a partial sweep around the axis like pbrt does
+
+// where a primitive sits in the world: the local origin and a frame whose w axis is the symmetry axis
+struct Placement {
+    origin: Vec3<f64>,
+    onb: Onb,
+}
+
+impl Placement {
+    fn new(origin: Vec3<f64>, axis: Vec3<f64>) -> Self {
+        Self { origin, onb: Onb::new(&axis) }
+    }
+
+    // the frame is a pure rotation plus translation, so ray parameters t are the same in both spaces
+    fn ray_to_local(&self, ray: &Ray) -> (Vec3<f64>, Vec3<f64>) {
+        (self.onb.to_local(&(ray.origin() - &self.origin)), self.onb.to_local(ray.direction()))
+    }
+
+    // a local error bound in world space, each world axis collects every local axis that feeds it
+    fn abs_transform(&self, error: &Vec3<f64>) -> Vec3<f64> {
+        self.onb.u().abs() * *error.x() + self.onb.v().abs() * *error.y() + self.onb.w().abs() * *error.z()
+    }
+
+    // box around a local space box, transformed corner by corner
+    fn bounding_box(&self, min: Vec3<f64>, max: Vec3<f64>) -> Aabb {
+        let mut bbox = Aabb::empty();
+        for corner in 0..8 {
+            let p = Vec3::new(
+                if corner & 1 == 0 { *min.x() } else { *max.x() },
+                if corner & 2 == 0 { *min.y() } else { *max.y() },
+                if corner & 4 == 0 { *min.z() } else { *max.z() },
+            );
+            let q = self.origin + self.onb.transform(&p);
+            bbox = Aabb::surrounding(&bbox, &Aabb::from_points(&q, &q));
+        }
+        bbox.pad_to_minimums()
+    }
+}
+
+// surface details of a hit in local space
+struct LocalHit {
+    t: f64,
+    normal: Vec3<f64>,  // outward, does not need to be normalized
+    u: f64,
+    v: f64,
+    dpdv: Vec3<f64>,    // dp/du is the same for every shape, see fill
+}
+
+impl LocalHit {
+    // keep whichever candidate is closest
+    fn closest(best: Option<LocalHit>, candidate: LocalHit) -> Option<LocalHit> {
+        match best {
+            Some(b) if b.t <= candidate.t => Some(b),
+            _ => Some(candidate),
+        }
+    }
+
+    fn fill(self, ray: &Ray, placement: &Placement, phi_max: f64, mat: &Rc<dyn Material>, hit_record: &mut HitRecord) {
+        hit_record.t = self.t;
+        hit_record.point = ray.at(self.t);
+
+        // the roots come out of a solver rather than a formula pbrt could bound exactly, so take a
+        // generous relative bound on the local point and carry it through the frame
+        let local = placement.onb.to_local(&(hit_record.point - placement.origin));
+        hit_record.error = placement.abs_transform(&(gamma(16) * local.abs())) + gamma(3) * hit_record.point.abs();
+
+        let outward_normal = placement.onb.transform(&self.normal).normalize();
+        hit_record.set_face_normal(ray, &outward_normal);
+
+        hit_record.u = self.u;
+        hit_record.v = self.v;
+
+        // every shape here takes u = phi / phi_max around the local z axis
+        hit_record.tangent = placement.onb.transform(&(phi_max * Vec3::new(-local.y(), *local.x(), 0.0)));
+        hit_record.bitangent = placement.onb.transform(&self.dpdv);
+        hit_record.mat = Rc::clone(mat);
+    }
+}
+
+// unit vector pointing away from the z axis in the plane of p, zero on the axis
+fn radial(p: &Vec3<f64>) -> Vec3<f64> {
+    let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+    if rho == 0.0 { Vec3::origin() } else { Vec3::new(p.x() / rho, p.y() / rho, 0.0) }
+}
+
+// angle around the z axis in [0, 2pi)
+fn phi_of(x: f64, y: f64) -> f64 {
+    let phi = y.atan2(x);
+    if phi < 0.0 { phi + 2.0 * PI } else { phi }
+}
+
+fn phi_max_radians(degrees: f64) -> f64 {
+    degrees_to_radians(degrees.clamp(0.0, 360.0))
+}
+
+// real roots of a t^2 + b t + c in increasing order, computed without the usual cancellation
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// largest real root of m^3 + a m^2 + b m + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // depress with m = x - a / 3 to get x^3 + p x + q
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    let x = if discriminant >= 0.0 {
+        // one real root (cardano)
+        let s = discriminant.sqrt();
+        (-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt()
+    } else {
+        // three real roots (trigonometric form), k = 0 is the largest
+        let r = (-p / 3.0).sqrt();
+        let phi = (3.0 * q / (2.0 * p) * (-3.0 / p).sqrt()).clamp(-1.0, 1.0).acos();
+        2.0 * r * (phi / 3.0).cos()
+    };
+
+    x - a / 3.0
+}
+
+// real roots of t^4 + b t^3 + c t^2 + d t + e (ferrari), polished with a couple of newton steps
+pub(crate) fn solve_quartic(b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    // depress with t = y - b / 4 to get y^4 + p y^2 + q y + r
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut push_quadratic = |qb: f64, qc: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, qb, qc) {
+            roots.push(y0);
+            roots.push(y1);
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // biquadratic, solve for y^2
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    push_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // the resolvent cubic turns the quartic into a product of two quadratics
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return Vec::new();
+        }
+        let s = (2.0 * m).sqrt();
+        push_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        push_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    let f = |t: f64| (((t + b) * t + c) * t + d) * t + e;
+    let df = |t: f64| ((4.0 * t + 3.0 * b) * t + 2.0 * c) * t + d;
+
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|y| {
+            let mut t = y - b / 4.0;
+            for _ in 0..2 {
+                let slope = df(t);
+                if slope != 0.0 {
+                    t -= f(t) / slope;
+                }
+            }
+            t
+        })
+        .collect();
+
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots
+}
+
+// ---------------------------------------------------------------------------------------------
+
+// cylinder of the given radius standing on base and reaching height along axis, optionally closed by two caps
+pub struct Cylinder {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: Rc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3<f64>, axis: Vec3<f64>, radius: f64, height: f64, capped: bool, mat: Rc<dyn Material>) -> Self {
+        Self {
+            placement: Placement::new(base, axis),
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped,
+            mat,
+        }
+    }
+
+    // only keep the part of the surface between phi = 0 and phi_max degrees
+    pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+        self.phi_max = phi_max_radians(phi_max);
+        self
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let (o, d) = self.placement.ray_to_local(ray);
+        let mut best = None;
+
+        // side: x^2 + y^2 = r^2
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+
+        // a ray parallel to the axis never crosses the side
+        let roots = if a > 0.0 { solve_quadratic(a, b, c) } else { None };
+        if let Some((t0, t1)) = roots {
+            for t in [t0, t1] {
+                let p = o + d * t;
+                let phi = phi_of(*p.x(), *p.y());
+                if interval.surrounds(t) && (0.0..=self.height).contains(p.z()) && phi <= self.phi_max {
+                    let dpdv = Vec3::new(0.0, 0.0, self.height);
+                    let side = LocalHit { t, normal: Vec3::new(*p.x(), *p.y(), 0.0), u: phi / self.phi_max, v: p.z() / self.height, dpdv };
+                    best = LocalHit::closest(best, side);
+                }
+            }
+        }
+
+        // caps at z = 0 and z = height
+        if self.capped && *d.z() != 0.0 {
+            for (z, facing) in [(0.0, -1.0), (self.height, 1.0)] {
+                let t = (z - o.z()) / d.z();
+                let p = o + d * t;
+                let rho2 = p.x() * p.x() + p.y() * p.y();
+                let phi = phi_of(*p.x(), *p.y());
+                if interval.surrounds(t) && rho2 <= self.radius * self.radius && phi <= self.phi_max {
+                    let dpdv = self.radius * radial(&p);
+                    let cap = LocalHit { t, normal: Vec3::new(0.0, 0.0, facing), u: phi / self.phi_max, v: rho2.sqrt() / self.radius, dpdv };
+                    best = LocalHit::closest(best, cap);
+                }
+            }
+        }
+
+        match best {
+            Some(local) => {
+                local.fill(ray, &self.placement, self.phi_max, &self.mat, hit_record);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.placement.bounding_box(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height))
+    }
+}
+
+// cone with its base disk centred on base and its apex height along axis, optionally closed at the base
+pub struct Cone {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: Rc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(base: Vec3<f64>, axis: Vec3<f64>, radius: f64, height: f64, capped: bool, mat: Rc<dyn Material>) -> Self {
+        Self {
+            placement: Placement::new(base, axis),
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped,
+            mat,
+        }
+    }
+
+    pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+        self.phi_max = phi_max_radians(phi_max);
+        self
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let (o, d) = self.placement.ray_to_local(ray);
+        let mut best = None;
+
+        // side: x^2 + y^2 = k (h - z)^2 with k = (r / h)^2
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let hz = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k * d.z() * hz);
+        let c = o.x() * o.x() + o.y() * o.y() - k * hz * hz;
+
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = o + d * t;
+                let phi = phi_of(*p.x(), *p.y());
+                if interval.surrounds(t) && (0.0..=self.height).contains(p.z()) && phi <= self.phi_max {
+                    // gradient of the implicit equation
+                    let normal = Vec3::new(*p.x(), *p.y(), k * (self.height - p.z()));
+
+                    // climbing the side also draws the point in towards the axis
+                    let dpdv = self.height * Vec3::new(0.0, 0.0, 1.0) - self.radius * radial(&p);
+                    let side = LocalHit { t, normal, u: phi / self.phi_max, v: p.z() / self.height, dpdv };
+                    best = LocalHit::closest(best, side);
+                }
+            }
+        }
+
+        if self.capped && *d.z() != 0.0 {
+            let t = -o.z() / d.z();
+            let p = o + d * t;
+            let rho2 = p.x() * p.x() + p.y() * p.y();
+            let phi = phi_of(*p.x(), *p.y());
+            if interval.surrounds(t) && rho2 <= self.radius * self.radius && phi <= self.phi_max {
+                let dpdv = self.radius * radial(&p);
+                let cap = LocalHit { t, normal: Vec3::new(0.0, 0.0, -1.0), u: phi / self.phi_max, v: rho2.sqrt() / self.radius, dpdv };
+                best = LocalHit::closest(best, cap);
+            }
+        }
+
+        match best {
+            Some(local) => {
+                local.fill(ray, &self.placement, self.phi_max, &self.mat, hit_record);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.placement.bounding_box(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height))
+    }
+}
+
+// flat disk facing along normal; an inner radius above zero turns it into an annulus (washer)
+pub struct Disk {
+    placement: Placement,
+    radius: f64,
+    inner_radius: f64,
+    phi_max: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3<f64>, normal: Vec3<f64>, radius: f64, mat: Rc<dyn Material>) -> Self {
+        Self::annulus(center, normal, 0.0, radius, mat)
+    }
+
+    pub fn annulus(center: Vec3<f64>, normal: Vec3<f64>, inner_radius: f64, radius: f64, mat: Rc<dyn Material>) -> Self {
+        Self {
+            placement: Placement::new(center, normal),
+            radius,
+            inner_radius,
+            phi_max: 2.0 * PI,
+            mat,
+        }
+    }
+
+    pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+        self.phi_max = phi_max_radians(phi_max);
+        self
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let (o, d) = self.placement.ray_to_local(ray);
+
+        // parallel to the disk
+        if *d.z() == 0.0 {
+            return false;
+        }
+
+        let t = -o.z() / d.z();
+        if !interval.surrounds(t) {
+            return false;
+        }
+
+        let p = o + d * t;
+        let rho2 = p.x() * p.x() + p.y() * p.y();
+        if rho2 > self.radius * self.radius || rho2 < self.inner_radius * self.inner_radius {
+            return false;
+        }
+
+        let phi = phi_of(*p.x(), *p.y());
+        if phi > self.phi_max {
+            return false;
+        }
+
+        // v runs from the outer edge (0) to the inner edge (1)
+        let v = (self.radius - rho2.sqrt()) / (self.radius - self.inner_radius);
+        let dpdv = -(self.radius - self.inner_radius) * radial(&p);
+        let local = LocalHit { t, normal: Vec3::new(0.0, 0.0, 1.0), u: phi / self.phi_max, v, dpdv };
+        local.fill(ray, &self.placement, self.phi_max, &self.mat, hit_record);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = self.radius;
+        self.placement.bounding_box(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, 0.0))
+    }
+}
+
+// ring around axis: the tube of minor_radius follows a circle of major_radius around center
+pub struct Torus {
+    placement: Placement,
+    major_radius: f64,
+    minor_radius: f64,
+    phi_max: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Vec3<f64>, axis: Vec3<f64>, major_radius: f64, minor_radius: f64, mat: Rc<dyn Material>) -> Self {
+        Self {
+            placement: Placement::new(center, axis),
+            major_radius,
+            minor_radius,
+            phi_max: 2.0 * PI,
+            mat,
+        }
+    }
+
+    pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+        self.phi_max = phi_max_radians(phi_max);
+        self
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let (o, d) = self.placement.ray_to_local(ray);
+        let length = d.mag();
+        if length == 0.0 {
+            return false;
+        }
+        let d = d / length;
+
+        // start the quartic from the point closest to the centre, this keeps the coefficients small
+        // and the roots accurate for rays coming from far away
+        let t_shift = -o.dot(&d);
+        let o = o + d * t_shift;
+
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with p = o + s d and |d| = 1
+        let f = o.dot(&d);
+        let g = o.dot(&o) + big_r2 - small_r2;
+        let dxy2 = d.x() * d.x() + d.y() * d.y();
+        let oxy_dxy = o.x() * d.x() + o.y() * d.y();
+        let oxy2 = o.x() * o.x() + o.y() * o.y();
+
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * g - 4.0 * big_r2 * dxy2,
+            4.0 * f * g - 8.0 * big_r2 * oxy_dxy,
+            g * g - 4.0 * big_r2 * oxy2,
+        );
+
+        for s in roots {
+            let t = (s + t_shift) / length;
+            if !interval.surrounds(t) {
+                continue;
+            }
+
+            let p = o + d * s;
+            let phi = phi_of(*p.x(), *p.y());
+            if phi > self.phi_max {
+                continue;
+            }
+
+            // the normal points away from the nearest point on the core circle
+            let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            let core = Vec3::new(p.x() / rho, p.y() / rho, 0.0) * self.major_radius;
+            let normal = p - core;
+
+            // theta goes around the tube, starting on the outside of the ring
+            let theta = phi_of(rho - self.major_radius, *p.z());
+
+            // normal (in the tube's cross section) turned a quarter towards growing theta
+            let dpdv = 2.0 * PI * Vec3::new(-p.z() * p.x() / rho, -p.z() * p.y() / rho, rho - self.major_radius);
+            let local = LocalHit { t, normal, u: phi / self.phi_max, v: theta / (2.0 * PI), dpdv };
+            local.fill(ray, &self.placement, self.phi_max, &self.mat, hit_record);
+            return true;
+        }
+
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = self.major_radius + self.minor_radius;
+        let h = self.minor_radius;
+        self.placement.bounding_box(Vec3::new(-r, -r, -h), Vec3::new(r, r, h))
+    }
+}
@@ -3,11 +3,23 @@ use crate::core::Vec3;
 pub struct Ray {
     origin: Vec3<f64>,
     direction: Vec3<f64>,
+    time: f64,          // the moment in the shutter interval this ray was fired at
+    cone_width: f64,    // the ray stands for a thin cone this wide at its origin,
+    cone_spread: f64,   // widening by this much per unit of distance (zero for both: an infinitely thin ray)
 }
 
 impl Ray {
     pub fn new(origin: Vec3<f64>, direction: Vec3<f64>) -> Self {
-        Ray { origin, direction }
+        Self::new_with_time(origin, direction, 0.0)
+    }
+
+    pub fn new_with_time(origin: Vec3<f64>, direction: Vec3<f64>, time: f64) -> Self {
+        Ray { origin, direction, time, cone_width: 0.0, cone_spread: 0.0 }
+    }
+
+    // the cone a camera ray covers (a pixel's worth of view), textures use it to pick how blurred a copy to read
+    pub fn with_cone(self, width: f64, spread: f64) -> Self {
+        Ray { cone_width: width, cone_spread: spread, ..self }
     }
 
     pub fn origin(&self) -> &Vec3<f64> {
@@ -18,7 +30,20 @@ impl Ray {
         &self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn cone_spread(&self) -> f64 {
+        self.cone_spread
+    }
+
+    // width of the cone at parameter t
+    pub fn cone_width(&self, t: f64) -> f64 {
+        self.cone_width + self.cone_spread * t * self.direction.mag()
+    }
+
     pub fn at(&self, t: f64) -> Vec3<f64> {
         self.origin + self.direction * t
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,56 @@
+use crate::camera::{Background, Camera};
+use crate::core::Vec3;
+use crate::cuboid::Cuboid;
+use crate::hittable_list::HittableList;
+use crate::material::{DiffuseLight, Lambertian};
+use crate::quad::Quad;
+use crate::transform::{RotateY, Translate};
+
+use std::rc::Rc;
+
+// reference scenes shared by the demo, tests and benchmarks
+
+// the classic cornell box: a 555 unit room with a red and a green wall, a ceiling light and two rotated boxes
+// the returned camera looks in through the open side, the render settings can be tweaked before rendering
+pub fn cornell_box() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let red = Rc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let white = Rc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let green = Rc::new(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::new(Vec3::new(15.0, 15.0, 15.0)));
+
+    // walls, floor and ceiling
+    world.add(Rc::new(Quad::new(Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Rc::new(Quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    world.add(Rc::new(Quad::new(Vec3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light)));
+    world.add(Rc::new(Quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+    world.add(Rc::new(Quad::new(Vec3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone())));
+    world.add(Rc::new(Quad::new(Vec3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+    // the tall box at the back and the short one at the front
+    let tall_box = Rc::new(Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 330.0, 165.0), white.clone()));
+    let tall_box = Rc::new(RotateY::new(tall_box, 15.0));
+    world.add(Rc::new(Translate::new(tall_box, Vec3::new(265.0, 0.0, 295.0))));
+
+    let short_box = Rc::new(Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(165.0, 165.0, 165.0), white));
+    let short_box = Rc::new(RotateY::new(short_box, -18.0));
+    world.add(Rc::new(Translate::new(short_box, Vec3::new(130.0, 0.0, 65.0))));
+
+    let mut cam = Camera::new();
+    cam.aspect_ratio = 1.0;
+    cam.image_width = 600;
+    cam.samples_per_pixel = 200;
+    cam.max_depth = 50;
+
+    cam.vfov = 40.0;
+    cam.lookfrom = Vec3::new(278.0, 278.0, -800.0);
+    cam.lookat = Vec3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+
+    // all the light comes from the ceiling lamp
+    cam.background = Background::Black;
+
+    (world, cam)
+}
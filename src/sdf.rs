@@ -0,0 +1,331 @@
+use crate::core::Vec3;
+
+use std::rc::Rc;
+
+// signed distance function: negative inside the shape, positive outside, zero on the surface
+// the value must never overestimate the distance to the surface or sphere tracing will step through it
+pub trait Sdf {
+    fn distance(&self, p: &Vec3<f64>) -> f64;
+
+    // direction of steepest increase, the outward normal on the surface
+    // the default uses central differences on a tetrahedron (4 evaluations instead of 6)
+    fn gradient(&self, p: &Vec3<f64>) -> Vec3<f64> {
+        let h = 1e-5;
+        let k0 = Vec3::new(1.0, -1.0, -1.0);
+        let k1 = Vec3::new(-1.0, -1.0, 1.0);
+        let k2 = Vec3::new(-1.0, 1.0, -1.0);
+        let k3 = Vec3::new(1.0, 1.0, 1.0);
+
+        k0 * self.distance(&(*p + k0 * h))
+            + k1 * self.distance(&(*p + k1 * h))
+            + k2 * self.distance(&(*p + k2 * h))
+            + k3 * self.distance(&(*p + k3 * h))
+    }
+}
+
+fn abs(v: &Vec3<f64>) -> Vec3<f64> {
+    Vec3::new(v.x().abs(), v.y().abs(), v.z().abs())
+}
+
+fn max_zero(v: &Vec3<f64>) -> Vec3<f64> {
+    Vec3::new(v.x().max(0.0), v.y().max(0.0), v.z().max(0.0))
+}
+
+// ---------------------------------------------------------------------------------------------
+// primitives, all centred on the origin; move them around with SdfTranslate
+
+pub struct SdfSphere {
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        p.mag() - self.radius
+    }
+
+    fn gradient(&self, p: &Vec3<f64>) -> Vec3<f64> {
+        *p
+    }
+}
+
+pub struct SdfBox {
+    half_extents: Vec3<f64>,
+}
+
+impl SdfBox {
+    pub fn new(half_extents: Vec3<f64>) -> Self {
+        Self { half_extents }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let q = abs(p) - self.half_extents;
+        max_zero(&q).mag() + q.x().max(q.y().max(*q.z())).min(0.0)
+    }
+}
+
+// box with its edges rounded off by radius, the overall size stays half_extents
+pub struct SdfRoundBox {
+    half_extents: Vec3<f64>,
+    radius: f64,
+}
+
+impl SdfRoundBox {
+    pub fn new(half_extents: Vec3<f64>, radius: f64) -> Self {
+        Self { half_extents, radius }
+    }
+}
+
+impl Sdf for SdfRoundBox {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let r = self.radius;
+        let q = abs(p) - self.half_extents + Vec3::new(r, r, r);
+        max_zero(&q).mag() + q.x().max(q.y().max(*q.z())).min(0.0) - r
+    }
+}
+
+// torus lying in the xz plane
+pub struct SdfTorus {
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(major_radius: f64, minor_radius: f64) -> Self {
+        Self { major_radius, minor_radius }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+}
+
+// line segment a-b swept by a sphere
+pub struct SdfCapsule {
+    a: Vec3<f64>,
+    b: Vec3<f64>,
+    radius: f64,
+}
+
+impl SdfCapsule {
+    pub fn new(a: Vec3<f64>, b: Vec3<f64>, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let pa = p - &self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(&ba) / ba.dot(&ba)).clamp(0.0, 1.0);
+        (pa - ba * h).mag() - self.radius
+    }
+}
+
+// power 8 is the classic look; this is a distance estimate so it needs a few more steps than the rest
+pub struct SdfMandelbulb {
+    power: f64,
+    iterations: u32,
+}
+
+impl SdfMandelbulb {
+    pub fn new(power: f64, iterations: u32) -> Self {
+        Self { power, iterations }
+    }
+}
+
+impl Sdf for SdfMandelbulb {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let mut z = *p;
+        let mut dr = 1.0;
+        let mut r = 0.0;
+
+        for _ in 0..self.iterations {
+            r = z.mag();
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+
+            // convert to polar coordinates, scale and rotate, then back again
+            let theta = (z.z() / r).acos() * self.power;
+            let phi = z.y().atan2(*z.x()) * self.power;
+            let zr = r.powf(self.power);
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+
+            z = Vec3::new(theta.sin() * phi.cos(), phi.sin() * theta.sin(), theta.cos()) * zr + *p;
+        }
+
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// combinators
+
+pub struct SdfUnion {
+    a: Rc<dyn Sdf>,
+    b: Rc<dyn Sdf>,
+}
+
+impl SdfUnion {
+    pub fn new(a: Rc<dyn Sdf>, b: Rc<dyn Sdf>) -> Self {
+        Self { a, b }
+    }
+}
+
+impl Sdf for SdfUnion {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+}
+
+pub struct SdfIntersection {
+    a: Rc<dyn Sdf>,
+    b: Rc<dyn Sdf>,
+}
+
+impl SdfIntersection {
+    pub fn new(a: Rc<dyn Sdf>, b: Rc<dyn Sdf>) -> Self {
+        Self { a, b }
+    }
+}
+
+impl Sdf for SdfIntersection {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        self.a.distance(p).max(self.b.distance(p))
+    }
+}
+
+// a with b carved out of it
+pub struct SdfDifference {
+    a: Rc<dyn Sdf>,
+    b: Rc<dyn Sdf>,
+}
+
+impl SdfDifference {
+    pub fn new(a: Rc<dyn Sdf>, b: Rc<dyn Sdf>) -> Self {
+        Self { a, b }
+    }
+}
+
+impl Sdf for SdfDifference {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        self.a.distance(p).max(-self.b.distance(p))
+    }
+}
+
+// union with a fillet of roughly size k where the shapes meet (polynomial smooth min)
+pub struct SdfSmoothUnion {
+    a: Rc<dyn Sdf>,
+    b: Rc<dyn Sdf>,
+    k: f64,
+}
+
+impl SdfSmoothUnion {
+    pub fn new(a: Rc<dyn Sdf>, b: Rc<dyn Sdf>, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl Sdf for SdfSmoothUnion {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        d2 + (d1 - d2) * h - self.k * h * (1.0 - h)
+    }
+}
+
+pub struct SdfTranslate {
+    sdf: Rc<dyn Sdf>,
+    offset: Vec3<f64>,
+}
+
+impl SdfTranslate {
+    pub fn new(sdf: Rc<dyn Sdf>, offset: Vec3<f64>) -> Self {
+        Self { sdf, offset }
+    }
+}
+
+impl Sdf for SdfTranslate {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        self.sdf.distance(&(p - &self.offset))
+    }
+}
+
+// rounds every edge of the shape by growing it outwards
+pub struct SdfRound {
+    sdf: Rc<dyn Sdf>,
+    radius: f64,
+}
+
+impl SdfRound {
+    pub fn new(sdf: Rc<dyn Sdf>, radius: f64) -> Self {
+        Self { sdf, radius }
+    }
+}
+
+impl Sdf for SdfRound {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        self.sdf.distance(p) - self.radius
+    }
+}
+
+// twists the shape around the y axis by rate radians per unit of height
+// this stretches distances, so trace it with a step scale below 1 (see SdfObject::with_step_scale)
+pub struct SdfTwist {
+    sdf: Rc<dyn Sdf>,
+    rate: f64,
+}
+
+impl SdfTwist {
+    pub fn new(sdf: Rc<dyn Sdf>, rate: f64) -> Self {
+        Self { sdf, rate }
+    }
+}
+
+impl Sdf for SdfTwist {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let (s, c) = (self.rate * p.y()).sin_cos();
+        let q = Vec3::new(c * p.x() - s * p.z(), *p.y(), s * p.x() + c * p.z());
+        self.sdf.distance(&q)
+    }
+}
+
+// repeats the shape forever on a grid with the given spacing, a spacing of 0 leaves that axis alone
+// the bounding box of the SdfObject decides how many copies are actually visible
+pub struct SdfRepeat {
+    sdf: Rc<dyn Sdf>,
+    spacing: Vec3<f64>,
+}
+
+impl SdfRepeat {
+    pub fn new(sdf: Rc<dyn Sdf>, spacing: Vec3<f64>) -> Self {
+        Self { sdf, spacing }
+    }
+}
+
+impl Sdf for SdfRepeat {
+    fn distance(&self, p: &Vec3<f64>) -> f64 {
+        let wrap = |x: f64, s: f64| if s > 0.0 { x - s * (x / s).round() } else { x };
+        let q = Vec3::new(
+            wrap(*p.x(), *self.spacing.x()),
+            wrap(*p.y(), *self.spacing.y()),
+            wrap(*p.z(), *self.spacing.z()),
+        );
+        self.sdf.distance(&q)
+    }
+}
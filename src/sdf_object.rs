@@ -0,0 +1,96 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::sdf::Sdf;
+use crate::core::Vec3;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// a shape given by a signed distance function, rendered by sphere tracing:
+// the distance at a point is a radius that is guaranteed empty, so the ray can safely jump that far
+pub struct SdfObject {
+    sdf: Rc<dyn Sdf>,
+    bounds: Aabb,       // the shape must lie inside this box, tracing only happens within it
+    mat: Rc<dyn Material>,
+    step_scale: f64,    // below 1 for functions that overestimate the distance (twists, fractals)
+}
+
+impl SdfObject {
+    const MAX_STEPS: u32 = 512;
+    const HIT_EPSILON: f64 = 1e-6;
+
+    pub fn new(sdf: Rc<dyn Sdf>, bounds: Aabb, mat: Rc<dyn Material>) -> Self {
+        Self::with_step_scale(sdf, bounds, mat, 1.0)
+    }
+
+    pub fn with_step_scale(sdf: Rc<dyn Sdf>, bounds: Aabb, mat: Rc<dyn Material>, step_scale: f64) -> Self {
+        Self { sdf, bounds, mat, step_scale }
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut ray_t = *interval;
+        if !self.bounds.hit(ray, &mut ray_t) {
+            return false;
+        }
+
+        let ray_length = ray.direction().mag();
+        let mut t = ray_t.min();
+
+        // march on the absolute distance so rays that start inside (refraction) find the way out too
+        let mut distance = self.sdf.distance(&ray.at(t));
+        let inside = if distance.abs() < Self::HIT_EPSILON {
+            // starting right on the surface: the side we are heading to decides, then step off it
+            let leaving = self.sdf.gradient(&ray.at(t)).dot(ray.direction()) > 0.0;
+            t += 2.0 * Self::HIT_EPSILON / ray_length;
+            distance = self.sdf.distance(&ray.at(t));
+            !leaving
+        } else {
+            distance < 0.0
+        };
+
+        let mut found = false;
+        for _ in 0..Self::MAX_STEPS {
+            let step = if inside { -distance } else { distance };
+
+            if step < Self::HIT_EPSILON {
+                found = true;
+                break;
+            }
+
+            t += step * self.step_scale / ray_length;
+            if t >= ray_t.max() {
+                return false;
+            }
+
+            distance = self.sdf.distance(&ray.at(t));
+        }
+
+        if !found || !interval.surrounds(t) {
+            return false;
+        }
+
+        hit_record.t = t;
+        hit_record.point = ray.at(t);
+
+        // marching stops anywhere within HIT_EPSILON of the surface
+        let tolerance = Self::HIT_EPSILON;
+        hit_record.error = Vec3::new(tolerance, tolerance, tolerance) + gamma(3) * hit_record.point.abs();
+
+        let outward_normal = self.sdf.gradient(&hit_record.point).normalize();
+        hit_record.set_face_normal(ray, &outward_normal);
+
+        // no uv here, give anisotropic materials a tangent running around the y axis as on a sphere
+        hit_record.tangent = Vec3::new(*outward_normal.z(), 0.0, -outward_normal.x());
+
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
@@ -1,29 +1,63 @@
-use crate::core::Vec3;
+use crate::aabb::Aabb;
+use crate::core::{Vec3, PI};
 use crate::hittable::{Hittable, HitRecord};
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::Material;
+use crate::{gamma, Material};
 
 use std::rc::Rc;
 
 pub struct Sphere {
-    center: Vec3<f64>,
+    center: Ray,        // the centre moves linearly from center.at(0) to center.at(1) over the shutter
     radius: f64,
-    mat: Rc<dyn Material>
+    mat: Rc<dyn Material>,
+    bbox: Aabb,
 }
 
 impl Sphere{
     pub fn new(center: Vec3<f64>, radius: f64, mat: Rc<dyn Material>) -> Self{
-        Self{center, radius, mat}
+        // a stationary sphere is just a moving one with no velocity
+        let rvec = Vec3::new(radius, radius, radius);
+        let bbox = Aabb::from_points(&(center - rvec), &(center + rvec));
+        Self{center: Ray::new(center, Vec3::origin()), radius, mat, bbox}
 
         // TODO: initialise the material pointer here
     }
+
+    // moving sphere: at time 0 it sits at center1, at time 1 at center2
+    pub fn new_moving(center1: Vec3<f64>, center2: Vec3<f64>, radius: f64, mat: Rc<dyn Material>) -> Self{
+        // the box has to hold the sphere at both ends of its motion
+        let rvec = Vec3::new(radius, radius, radius);
+        let box1 = Aabb::from_points(&(center1 - rvec), &(center1 + rvec));
+        let box2 = Aabb::from_points(&(center2 - rvec), &(center2 + rvec));
+        Self{center: Ray::new(center1, center2 - center1), radius, mat, bbox: Aabb::surrounding(&box1, &box2)}
+    }
+
+    // p is a point on the unit sphere, u runs around the y axis from -x, v from the bottom (-y) to the top
+    pub(crate) fn get_sphere_uv(p: &Vec3<f64>) -> (f64, f64){
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(*p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // dp/du and dp/dv at a point relative to the centre: u is the angle around y over 2 pi, so the point
+    // circles the y axis as u grows, v the angle down from the south pole over pi; both vanish at the poles
+    pub(crate) fn sphere_derivatives(local: &Vec3<f64>) -> (Vec3<f64>, Vec3<f64>) {
+        let dpdu = 2.0 * PI * Vec3::new(*local.z(), 0.0, -local.x());
+        let rho = (local.x() * local.x() + local.z() * local.z()).sqrt();
+        if rho == 0.0 {
+            return (dpdu, Vec3::origin());
+        }
+        let dpdv = PI * Vec3::new(-local.x() * local.y() / rho, rho, -local.z() * local.y() / rho);
+        (dpdu, dpdv)
+    }
 }
 // remember to use Hittable trait in code, we import  it from hittable.rs
 impl Hittable for Sphere{
     fn hit(&self, ray: &Ray,interval: &Interval, hit_record: &mut HitRecord)-> bool {
        
-        let oc = &self.center - ray.origin();
+        let current_center = self.center.at(ray.time());
+        let oc = &current_center - ray.origin();
 
 
         let a = ray.direction().dot(ray.direction());
@@ -49,14 +83,25 @@ impl Hittable for Sphere{
         }
         
         hit_record.t = root;
-        hit_record.point = ray.at(root);
+
+        // put the point back on the sphere, which leaves a much smaller error than ray.at(root)
+        let local = ray.at(root) - current_center;
+        let local = local * (self.radius / local.mag());
+        hit_record.point = current_center + local;
+        hit_record.error = gamma(5) * local.abs() + gamma(1) * hit_record.point.abs();
 
         // Calculate the normal at the intersection point
-        let  outward_normal = (hit_record.point - self.center)/self.radius ;
+        let  outward_normal = local / self.radius ;
         hit_record.set_face_normal(&ray, &outward_normal);
+        (hit_record.u, hit_record.v) = Self::get_sphere_uv(&outward_normal);
+        (hit_record.tangent, hit_record.bitangent) = Self::sphere_derivatives(&local);
 
         // clone the smart pointer: increase the reference count
         hit_record.mat = Rc::clone(&self.mat);
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
\ No newline at end of file
@@ -0,0 +1,132 @@
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::{gamma, Material};
+
+use std::rc::Rc;
+
+// many small spheres sharing one material, e.g. particles out of a simulation
+// centres, radii and colours sit in flat f32 arrays (16 bytes a particle, 28 with colour, plus the bvh) instead of
+// one Sphere and one material each; hits report the particle in hit_record.index
+pub struct SphereCloud {
+    centers: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    bvh: Bvh,
+    mat: Rc<dyn Material>,
+}
+
+impl SphereCloud {
+    pub fn new(centers: Vec<[f32; 3]>, radii: Vec<f32>, mat: Rc<dyn Material>) -> Self {
+        assert_eq!(centers.len(), radii.len(), "every particle needs a radius");
+
+        let boxes: Vec<Aabb> = centers
+            .iter()
+            .zip(radii.iter())
+            .map(|(c, &r)| {
+                let r = r as f64;
+                let (x, y, z) = (c[0] as f64, c[1] as f64, c[2] as f64);
+                Aabb::new(Interval::new(x - r, x + r), Interval::new(y - r, y + r), Interval::new(z - r, z + r))
+            })
+            .collect();
+
+        Self { bvh: Bvh::new(&boxes), centers, radii, mat }
+    }
+
+    // same as new, with each particle's colour multiplying whatever the material reflects or emits
+    pub fn with_colors(centers: Vec<[f32; 3]>, radii: Vec<f32>, colors: Vec<[f32; 3]>, mat: Rc<dyn Material>) -> Self {
+        assert_eq!(centers.len(), colors.len(), "every particle needs a colour");
+        Self::new(centers, radii, Rc::new(ParticleTint { inner: mat, colors }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    fn center(&self, i: usize) -> Vec3<f64> {
+        let c = self.centers[i];
+        Vec3::new(c[0] as f64, c[1] as f64, c[2] as f64)
+    }
+}
+
+impl Hittable for SphereCloud {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut closest = None;
+
+        // same quadratic as Sphere::hit
+        self.bvh.hit(ray, interval, |i, ray_t| {
+            let radius = self.radii[i] as f64;
+            let oc = self.center(i) - *ray.origin();
+            let a = ray.direction().dot(ray.direction());
+            let h = ray.direction().dot(&oc);
+            let c = oc.dot(&oc) - radius * radius;
+            let discriminant = h * h - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+
+            let sqrt_d = discriminant.sqrt();
+            let t = [(h - sqrt_d) / a, (h + sqrt_d) / a].into_iter().find(|&t| ray_t.surrounds(t))?;
+            closest = Some((i, t));
+            Some(t)
+        });
+
+        let Some((i, t)) = closest else {
+            return false;
+        };
+
+        // reprojected onto the particle like Sphere::hit does
+        let radius = self.radii[i] as f64;
+        let local = ray.at(t) - self.center(i);
+        let local = local * (radius / local.mag());
+
+        hit_record.t = t;
+        hit_record.point = self.center(i) + local;
+        hit_record.error = gamma(5) * local.abs() + gamma(1) * hit_record.point.abs();
+        let outward_normal = local / radius;
+        hit_record.set_face_normal(ray, &outward_normal);
+        (hit_record.u, hit_record.v) = Sphere::get_sphere_uv(&outward_normal);
+        (hit_record.tangent, hit_record.bitangent) = Sphere::sphere_derivatives(&local);
+        hit_record.index = i;
+        hit_record.mat = Rc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+// tints the wrapped material by the colour of the particle that was hit
+struct ParticleTint {
+    inner: Rc<dyn Material>,
+    colors: Vec<[f32; 3]>,
+}
+
+impl ParticleTint {
+    fn color(&self, hit_record: &HitRecord) -> Vec3<f64> {
+        let c = self.colors[hit_record.index];
+        Vec3::new(c[0] as f64, c[1] as f64, c[2] as f64)
+    }
+}
+
+impl Material for ParticleTint {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        if !self.inner.scatter(ray_in, hit_record, attenuation, scattered) {
+            return false;
+        }
+        *attenuation = *attenuation * self.color(hit_record);
+        true
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Vec3<f64> {
+        self.inner.emitted(ray_in, hit_record) * self.color(hit_record)
+    }
+}
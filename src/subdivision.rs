@@ -0,0 +1,453 @@
+use crate::core::{Vec3, INFINITY, PI};
+use crate::mesh::Mesh;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubdivisionScheme {
+    CatmullClark,       // any polygons, produces quads
+    Loop,               // triangles, other polygons are fanned into triangles first
+}
+
+// refines a control cage the given number of times and then moves every vertex onto the limit surface
+// with the exact limit normal, so a TriangleMesh built from the result shades smoothly
+// boundary edges and creased edges follow the cubic b-spline crease rules, a vertex where more than
+// two of them meet stays put as a corner; crease sharpness drops by one each level (DeRose et al.)
+// uvs are interpolated linearly inside each face, incoming normals are replaced
+pub fn subdivide(mesh: &Mesh, scheme: SubdivisionScheme, levels: usize) -> Mesh {
+    if levels == 0 {
+        return mesh.clone();
+    }
+
+    let mut current = match scheme {
+        SubdivisionScheme::CatmullClark => mesh.clone(),
+        SubdivisionScheme::Loop => mesh.triangulate(),
+    };
+    current.normals = None;
+
+    for _ in 0..levels {
+        current = match scheme {
+            SubdivisionScheme::CatmullClark => catmull_clark_step(&current),
+            SubdivisionScheme::Loop => loop_step(&current),
+        };
+    }
+
+    push_to_limit(&mut current, scheme);
+    current
+}
+
+// edge connectivity of a polygon mesh
+struct Topology {
+    edges: HashMap<(usize, usize), usize>,      // sorted vertex pair -> edge index
+    edge_vertices: Vec<(usize, usize)>,
+    edge_faces: Vec<Vec<usize>>,
+    edge_creases: Vec<f64>,                     // sharpness given by the mesh, 0 for smooth edges
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(mesh: &Mesh) -> Self {
+        let mut topology = Self {
+            edges: HashMap::new(),
+            edge_vertices: Vec::new(),
+            edge_faces: Vec::new(),
+            edge_creases: Vec::new(),
+            vertex_edges: vec![Vec::new(); mesh.positions.len()],
+            vertex_faces: vec![Vec::new(); mesh.positions.len()],
+        };
+
+        for (f, face) in mesh.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let key = (a.min(b), a.max(b));
+
+                let e = match topology.edges.get(&key) {
+                    Some(&e) => e,
+                    None => {
+                        let e = topology.edge_vertices.len();
+                        topology.edges.insert(key, e);
+                        topology.edge_vertices.push(key);
+                        topology.edge_faces.push(Vec::new());
+                        topology.edge_creases.push(0.0);
+                        topology.vertex_edges[a].push(e);
+                        topology.vertex_edges[b].push(e);
+                        e
+                    }
+                };
+                topology.edge_faces[e].push(f);
+                topology.vertex_faces[a].push(f);
+            }
+        }
+
+        for &(a, b, sharpness) in mesh.creases.iter() {
+            if let Some(&e) = topology.edges.get(&(a.min(b), a.max(b))) {
+                topology.edge_creases[e] = topology.edge_creases[e].max(sharpness);
+            }
+        }
+
+        topology
+    }
+
+    fn edge(&self, a: usize, b: usize) -> usize {
+        self.edges[&(a.min(b), a.max(b))]
+    }
+
+    // boundary and non-manifold edges are infinitely sharp
+    fn sharpness(&self, e: usize) -> f64 {
+        if self.edge_faces[e].len() != 2 { INFINITY } else { self.edge_creases[e] }
+    }
+
+    fn other_end(&self, e: usize, v: usize) -> usize {
+        let (a, b) = self.edge_vertices[e];
+        if a == v { b } else { a }
+    }
+
+    fn sharp_edges(&self, v: usize) -> Vec<usize> {
+        self.vertex_edges[v].iter().cloned().filter(|&e| self.sharpness(e) > 0.0).collect()
+    }
+
+    // edge point from the smooth rule, pulled towards the midpoint by the edge's sharpness
+    fn edge_rule(&self, positions: &[Vec3<f64>], e: usize, smooth: impl FnOnce() -> Vec3<f64>) -> Vec3<f64> {
+        let (a, b) = self.edge_vertices[e];
+        let midpoint = 0.5 * (positions[a] + positions[b]);
+        let s = self.sharpness(e);
+        if s >= 1.0 {
+            return midpoint;
+        }
+        (1.0 - s) * smooth() + s * midpoint
+    }
+
+    // vertex point: smooth with fewer than two sharp edges, crease rule with two, corner with more
+    fn vertex_rule(&self, positions: &[Vec3<f64>], v: usize, smooth: Vec3<f64>) -> Vec3<f64> {
+        let p = positions[v];
+        let sharp = self.sharp_edges(v);
+        if sharp.len() < 2 {
+            return smooth;
+        }
+
+        let s = (sharp.iter().map(|&e| self.sharpness(e)).sum::<f64>() / sharp.len() as f64).min(1.0);
+        let sharp_point = if sharp.len() == 2 {
+            (positions[self.other_end(sharp[0], v)] + 6.0 * p + positions[self.other_end(sharp[1], v)]) / 8.0
+        } else {
+            p
+        };
+        (1.0 - s) * smooth + s * sharp_point
+    }
+
+    // child crease edges of every creased edge, one level softer
+    fn child_creases(&self, vertex_point: impl Fn(usize) -> usize, edge_point: impl Fn(usize) -> usize) -> Vec<(usize, usize, f64)> {
+        let mut creases = Vec::new();
+        for (e, &sharpness) in self.edge_creases.iter().enumerate() {
+            if sharpness > 1.0 {
+                let (a, b) = self.edge_vertices[e];
+                creases.push((vertex_point(a), edge_point(e), sharpness - 1.0));
+                creases.push((edge_point(e), vertex_point(b), sharpness - 1.0));
+            }
+        }
+        creases
+    }
+}
+
+fn average(points: impl Iterator<Item = Vec3<f64>>) -> Vec3<f64> {
+    let (sum, count) = points.fold((Vec3::origin(), 0), |(sum, count), p| (sum + p, count + 1));
+    sum / count.max(1) as f64
+}
+
+fn mid_uv(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])]
+}
+
+// new vertices are laid out as [old vertex points, edge points, face points]
+fn catmull_clark_step(mesh: &Mesh) -> Mesh {
+    let topology = Topology::new(mesh);
+    let positions = &mesh.positions;
+    let vertex_count = positions.len();
+    let edge_count = topology.edge_vertices.len();
+
+    let face_points: Vec<Vec3<f64>> = mesh.faces.iter().map(|face| average(face.iter().map(|&i| positions[i]))).collect();
+
+    let edge_points: Vec<Vec3<f64>> = (0..edge_count)
+        .map(|e| {
+            topology.edge_rule(positions, e, || {
+                let (a, b) = topology.edge_vertices[e];
+                let faces = &topology.edge_faces[e];
+                0.25 * (positions[a] + positions[b] + face_points[faces[0]] + face_points[faces[1]])
+            })
+        })
+        .collect();
+
+    let vertex_points: Vec<Vec3<f64>> = (0..vertex_count)
+        .map(|v| {
+            let n = topology.vertex_edges[v].len();
+            if n == 0 {
+                return positions[v];
+            }
+            let q = average(topology.vertex_faces[v].iter().map(|&f| face_points[f]));
+            let r = average(topology.vertex_edges[v].iter().map(|&e| 0.5 * (positions[v] + positions[topology.other_end(e, v)])));
+            let smooth = (q + 2.0 * r + (n as f64 - 3.0) * positions[v]) / n as f64;
+            topology.vertex_rule(positions, v, smooth)
+        })
+        .collect();
+
+    let edge_point = |e: usize| vertex_count + e;
+    let face_point = |f: usize| vertex_count + edge_count + f;
+
+    let mut faces = Vec::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let m = face.len();
+        for i in 0..m {
+            let (prev, here, next) = (face[(i + m - 1) % m], face[i], face[(i + 1) % m]);
+            faces.push(vec![here, edge_point(topology.edge(here, next)), face_point(f), edge_point(topology.edge(prev, here))]);
+        }
+    }
+
+    let uvs = mesh.uvs.as_ref().map(|uvs| {
+        let mut children = Vec::new();
+        for corners in uvs.iter() {
+            let m = corners.len();
+            let center = corners.iter().fold([0.0, 0.0], |c, uv| [c[0] + uv[0] / m as f64, c[1] + uv[1] / m as f64]);
+            for i in 0..m {
+                let (prev, here, next) = (corners[(i + m - 1) % m], corners[i], corners[(i + 1) % m]);
+                children.push(vec![here, mid_uv(here, next), center, mid_uv(prev, here)]);
+            }
+        }
+        children
+    });
+
+    Mesh {
+        positions: vertex_points.into_iter().chain(edge_points).chain(face_points).collect(),
+        faces,
+        uvs,
+        normals: None,
+        creases: topology.child_creases(|v| v, edge_point),
+    }
+}
+
+// loop's weight for the neighbours of a smooth vertex of valence n
+fn loop_beta(n: usize) -> f64 {
+    let n = n as f64;
+    let c = 0.375 + 0.25 * (2.0 * PI / n).cos();
+    (0.625 - c * c) / n
+}
+
+// new vertices are laid out as [old vertex points, edge points]
+fn loop_step(mesh: &Mesh) -> Mesh {
+    let topology = Topology::new(mesh);
+    let positions = &mesh.positions;
+    let vertex_count = positions.len();
+
+    let opposite = |f: usize, a: usize, b: usize| mesh.faces[f].iter().cloned().find(|&i| i != a && i != b).unwrap_or(a);
+
+    let edge_points: Vec<Vec3<f64>> = (0..topology.edge_vertices.len())
+        .map(|e| {
+            topology.edge_rule(positions, e, || {
+                let (a, b) = topology.edge_vertices[e];
+                let faces = &topology.edge_faces[e];
+                let (c, d) = (opposite(faces[0], a, b), opposite(faces[1], a, b));
+                0.375 * (positions[a] + positions[b]) + 0.125 * (positions[c] + positions[d])
+            })
+        })
+        .collect();
+
+    let vertex_points: Vec<Vec3<f64>> = (0..vertex_count)
+        .map(|v| {
+            let n = topology.vertex_edges[v].len();
+            if n == 0 {
+                return positions[v];
+            }
+            let beta = loop_beta(n);
+            let ring = topology.vertex_edges[v].iter().fold(Vec3::origin(), |sum, &e| sum + positions[topology.other_end(e, v)]);
+            let smooth = (1.0 - n as f64 * beta) * positions[v] + beta * ring;
+            topology.vertex_rule(positions, v, smooth)
+        })
+        .collect();
+
+    let edge_point = |e: usize| vertex_count + e;
+
+    let mut faces = Vec::new();
+    for face in mesh.faces.iter() {
+        let [a, b, c] = [face[0], face[1], face[2]];
+        let (ab, bc, ca) = (edge_point(topology.edge(a, b)), edge_point(topology.edge(b, c)), edge_point(topology.edge(c, a)));
+        faces.extend([vec![a, ab, ca], vec![ab, b, bc], vec![ca, bc, c], vec![ab, bc, ca]]);
+    }
+
+    let uvs = mesh.uvs.as_ref().map(|uvs| {
+        let mut children = Vec::new();
+        for corners in uvs.iter() {
+            let [a, b, c] = [corners[0], corners[1], corners[2]];
+            let (ab, bc, ca) = (mid_uv(a, b), mid_uv(b, c), mid_uv(c, a));
+            children.extend([vec![a, ab, ca], vec![ab, b, bc], vec![ca, bc, c], vec![ab, bc, ca]]);
+        }
+        children
+    });
+
+    Mesh {
+        positions: vertex_points.into_iter().chain(edge_points).collect(),
+        faces,
+        uvs,
+        normals: None,
+        creases: topology.child_creases(|v| v, edge_point),
+    }
+}
+
+// neighbours of a smooth interior vertex in counter-clockwise order, each with the vertex diagonally
+// across the following face when that face is a quad; None when the faces around v do not close up
+fn one_ring(mesh: &Mesh, topology: &Topology, directed: &HashMap<(usize, usize), usize>, v: usize) -> Option<Vec<(usize, Option<usize>)>> {
+    let start = *topology.vertex_faces[v].first()?;
+    let mut ring = Vec::new();
+    let mut f = start;
+
+    loop {
+        let face = &mesh.faces[f];
+        let m = face.len();
+        let i = face.iter().position(|&k| k == v)?;
+        ring.push((face[(i + 1) % m], if m == 4 { Some(face[(i + 2) % 4]) } else { None }));
+
+        // the next face around v is on the other side of the edge coming into v
+        f = *directed.get(&(v, face[(i + m - 1) % m]))?;
+        if f == start {
+            return Some(ring);
+        }
+        if ring.len() > topology.vertex_faces[v].len() {
+            return None;
+        }
+    }
+}
+
+// newell's method, the length is twice the polygon's area
+fn face_normal(mesh: &Mesh, face: &[usize]) -> Vec3<f64> {
+    let mut normal = Vec3::origin();
+    for (i, &a) in face.iter().enumerate() {
+        let (p, q) = (mesh.positions[a], mesh.positions[face[(i + 1) % face.len()]]);
+        normal += p.cross(&q);
+    }
+    normal
+}
+
+// replaces positions with their limit positions and sets per corner limit normals
+// smooth vertices use the analytic limit masks; on creases, boundaries and corners the normal is averaged
+// over the faces on each side of the sharp edges so the crease stays visibly hard
+fn push_to_limit(mesh: &mut Mesh, scheme: SubdivisionScheme) {
+    let topology = Topology::new(mesh);
+    let positions = &mesh.positions;
+
+    let mut directed = HashMap::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        for (i, &a) in face.iter().enumerate() {
+            directed.insert((a, face[(i + 1) % face.len()]), f);
+        }
+    }
+
+    let face_normals: Vec<Vec3<f64>> = mesh.faces.iter().map(|face| face_normal(mesh, face)).collect();
+
+    let mut limit_positions = positions.clone();
+    let mut vertex_normals: Vec<Option<Vec3<f64>>> = vec![None; positions.len()];
+
+    for v in 0..positions.len() {
+        let p = positions[v];
+        let sharp = topology.sharp_edges(v);
+
+        if sharp.len() == 2 {
+            let (a, b) = (positions[topology.other_end(sharp[0], v)], positions[topology.other_end(sharp[1], v)]);
+            limit_positions[v] = (a + 4.0 * p + b) / 6.0;
+            continue;
+        }
+        if sharp.len() > 2 {
+            continue;
+        }
+
+        let Some(ring) = one_ring(mesh, &topology, &directed, v) else {
+            continue;
+        };
+        let n = ring.len();
+        let angle = |i: usize| 2.0 * PI * i as f64 / n as f64;
+
+        let (position, t1, t2) = match scheme {
+            SubdivisionScheme::CatmullClark => {
+                if ring.iter().any(|(_, diagonal)| diagonal.is_none()) {
+                    continue;
+                }
+                let nf = n as f64;
+                let a_n = 1.0 + angle(1).cos() + (PI / nf).cos() * (2.0 * (9.0 + angle(1).cos())).sqrt();
+
+                let mut edge_sum = Vec3::origin();
+                let mut diagonal_sum = Vec3::origin();
+                let mut t1 = Vec3::origin();
+                let mut t2 = Vec3::origin();
+                for (i, &(e, diagonal)) in ring.iter().enumerate() {
+                    let (e, d) = (positions[e], positions[diagonal.unwrap_or(e)]);
+                    edge_sum += e;
+                    diagonal_sum += d;
+                    t1 += a_n * angle(i).cos() * e + (angle(i).cos() + angle(i + 1).cos()) * d;
+                    t2 += a_n * angle(i).sin() * e + (angle(i).sin() + angle(i + 1).sin()) * d;
+                }
+                ((nf * nf * p + 4.0 * edge_sum + diagonal_sum) / (nf * (nf + 5.0)), t1, t2)
+            }
+            SubdivisionScheme::Loop => {
+                let chi = 1.0 / (0.375 / loop_beta(n) + n as f64);
+
+                let mut ring_sum = Vec3::origin();
+                let mut t1 = Vec3::origin();
+                let mut t2 = Vec3::origin();
+                for (i, &(e, _)) in ring.iter().enumerate() {
+                    ring_sum += positions[e];
+                    t1 += angle(i).cos() * positions[e];
+                    t2 += angle(i).sin() * positions[e];
+                }
+                ((1.0 - n as f64 * chi) * p + chi * ring_sum, t1, t2)
+            }
+        };
+
+        limit_positions[v] = position;
+
+        // the masks fix the normal's line, the surrounding faces fix its side
+        let normal = t1.cross(&t2);
+        let facing: Vec3<f64> = topology.vertex_faces[v].iter().fold(Vec3::origin(), |sum, &f| sum + face_normals[f]);
+        if normal.mag() > 0.0 {
+            vertex_normals[v] = Some(if normal.dot(&facing) < 0.0 { -normal.normalize() } else { normal.normalize() });
+        }
+    }
+
+    // every other corner averages the faces it can reach around its vertex without crossing a sharp edge
+    let mut corner_normals: Vec<Vec<Vec3<f64>>> = mesh.faces.iter().map(|face| vec![Vec3::origin(); face.len()]).collect();
+    for (v, faces) in topology.vertex_faces.iter().enumerate() {
+        let mut sector: Vec<usize> = (0..faces.len()).collect();
+        let root = |sector: &Vec<usize>, mut i: usize| {
+            while sector[i] != i {
+                i = sector[i];
+            }
+            i
+        };
+
+        for &e in topology.vertex_edges[v].iter() {
+            if topology.sharpness(e) > 0.0 {
+                continue;
+            }
+            let (f, g) = (topology.edge_faces[e][0], topology.edge_faces[e][1]);
+            let (Some(i), Some(j)) = (faces.iter().position(|&k| k == f), faces.iter().position(|&k| k == g)) else {
+                continue;
+            };
+            let (ri, rj) = (root(&sector, i), root(&sector, j));
+            sector[ri] = rj;
+        }
+
+        let mut sums = vec![Vec3::origin(); faces.len()];
+        for (i, &f) in faces.iter().enumerate() {
+            let r = root(&sector, i);
+            sums[r] += face_normals[f];
+        }
+
+        for (i, &f) in faces.iter().enumerate() {
+            let normal = match vertex_normals[v] {
+                Some(normal) => normal,
+                None => sums[root(&sector, i)].normalize(),
+            };
+            let corner = mesh.faces[f].iter().position(|&k| k == v).unwrap_or(0);
+            corner_normals[f][corner] = normal;
+        }
+    }
+
+    mesh.positions = limit_positions;
+    mesh.normals = Some(corner_normals);
+}
@@ -0,0 +1,477 @@
+use crate::core::{Vec3, PI};
+use crate::hittable::HitRecord;
+use crate::image::Image;
+use crate::perlin::Perlin;
+
+use std::path::Path;
+use std::rc::Rc;
+
+// a colour that can vary over a surface, looked up by the hit's surface coordinates (u, v) and its position
+pub trait Texture
+{
+    fn value(&self, u: f64, v: f64, p: &Vec3<f64>) -> Vec3<f64>;
+
+    // a single number, for textures that drive a material parameter rather than a colour
+    fn scalar(&self, u: f64, v: f64, p: &Vec3<f64>) -> f64
+    {
+        let c = self.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+
+    // the lookup materials make: the hit also says how much of the surface its ray covers there,
+    // which textures that can filter (ImageTexture) use, the rest just read the point
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        self.value(hit_record.u, hit_record.v, &hit_record.point)
+    }
+
+    fn scalar_at(&self, hit_record: &HitRecord) -> f64
+    {
+        let c = self.value_at(hit_record);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+}
+
+// the same colour everywhere, what materials built from a plain colour use
+pub struct SolidColor
+{
+    albedo: Vec3<f64>,
+}
+
+impl SolidColor
+{
+    pub fn new(albedo: Vec3<f64>) -> Self
+    {
+        Self{albedo}
+    }
+
+    pub fn from_rgb(red: f64, green: f64, blue: f64) -> Self
+    {
+        Self::new(Vec3::new(red, green, blue))
+    }
+}
+
+impl Texture for SolidColor
+{
+    fn value(&self, _u: f64, _v: f64, _p: &Vec3<f64>) -> Vec3<f64>
+    {
+        self.albedo
+    }
+}
+
+// checkerboard in space: alternating cubes of side scale, so it shows on any shape without uvs
+pub struct Checker
+{
+    inv_scale: f64,
+    even: Rc<dyn Texture>,
+    odd: Rc<dyn Texture>,
+}
+
+impl Checker
+{
+    pub fn new(scale: f64, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self
+    {
+        Self{inv_scale: 1.0 / scale, even, odd}
+    }
+
+    pub fn from_colors(scale: f64, even: Vec3<f64>, odd: Vec3<f64>) -> Self
+    {
+        Self::new(scale, Rc::new(SolidColor::new(even)), Rc::new(SolidColor::new(odd)))
+    }
+
+    fn pick(&self, p: &Vec3<f64>) -> &Rc<dyn Texture>
+    {
+        let cell = |x: f64| (x * self.inv_scale).floor() as i64;
+        let sum = cell(*p.x()) + cell(*p.y()) + cell(*p.z());
+
+        if sum.rem_euclid(2) == 0 { &self.even } else { &self.odd }
+    }
+}
+
+impl Texture for Checker
+{
+    fn value(&self, u: f64, v: f64, p: &Vec3<f64>) -> Vec3<f64>
+    {
+        self.pick(p).value(u, v, p)
+    }
+
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        self.pick(&hit_record.point).value_at(hit_record)
+    }
+}
+
+// checkerboard in surface coordinates: columns squares across u and rows across v
+pub struct UvChecker
+{
+    columns: f64,
+    rows: f64,
+    even: Rc<dyn Texture>,
+    odd: Rc<dyn Texture>,
+}
+
+impl UvChecker
+{
+    pub fn new(columns: u32, rows: u32, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self
+    {
+        Self{columns: columns as f64, rows: rows as f64, even, odd}
+    }
+
+    pub fn from_colors(columns: u32, rows: u32, even: Vec3<f64>, odd: Vec3<f64>) -> Self
+    {
+        Self::new(columns, rows, Rc::new(SolidColor::new(even)), Rc::new(SolidColor::new(odd)))
+    }
+
+    fn pick(&self, u: f64, v: f64) -> &Rc<dyn Texture>
+    {
+        let sum = (u * self.columns).floor() as i64 + (v * self.rows).floor() as i64;
+
+        if sum.rem_euclid(2) == 0 { &self.even } else { &self.odd }
+    }
+}
+
+impl Texture for UvChecker
+{
+    fn value(&self, u: f64, v: f64, p: &Vec3<f64>) -> Vec3<f64>
+    {
+        self.pick(u, v).value(u, v, p)
+    }
+
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        self.pick(hit_record.u, hit_record.v).value_at(hit_record)
+    }
+}
+
+// what an image texture does with coordinates outside [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode
+{
+    Repeat,     // tile the image
+    Clamp,      // stretch the border texels outwards
+    Mirror,     // tile, flipping every other copy so the seams match
+}
+
+impl WrapMode
+{
+    // texel index i folded into 0..n
+    fn apply(&self, i: i64, n: usize) -> usize
+    {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+        };
+        i as usize
+    }
+}
+
+// one level of the mip pyramid
+struct MipLevel
+{
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3<f64>>,
+}
+
+// a picture wrapped over the surface by (u, v), v = 0 is the bottom row of the image
+// lookups are bilinear; a pyramid of half size copies, each a box filtered version of the one above, lets
+// sample() read a pre-blurred level when a lookup covers many texels instead of aliasing
+pub struct ImageTexture
+{
+    levels: Vec<MipLevel>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture
+{
+    pub fn new(image: &Image, wrap: WrapMode) -> Self
+    {
+        let (width, height) = (image.width(), image.height());
+        let texels = (0..height).flat_map(|y| (0..width).map(move |x| image.pixel(x, y))).collect();
+        let mut levels = vec![MipLevel{width, height, texels}];
+
+        while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1)
+        {
+            let width = last.width.div_ceil(2);
+            let height = last.height.div_ceil(2);
+            let (columns, rows) = (box_weights(last.width, width), box_weights(last.height, height));
+
+            let mut texels = Vec::with_capacity(width * height);
+            for row in rows.iter()
+            {
+                for column in columns.iter()
+                {
+                    let mut sum = Vec3::origin();
+                    for &(y, wy) in row.iter()
+                    {
+                        for &(x, wx) in column.iter()
+                        {
+                            sum += (wx * wy) * last.texels[y * last.width + x];
+                        }
+                    }
+                    texels.push(sum);
+                }
+            }
+            levels.push(MipLevel{width, height, texels});
+        }
+
+        Self{levels, wrap}
+    }
+
+    // loads any format Image reads, 8 and 16 bit files are taken to be srgb and decoded to linear
+    pub fn load(path: impl AsRef<Path>, wrap: WrapMode) -> std::io::Result<Self>
+    {
+        Ok(Self::new(&Image::load(path)?.srgb_to_linear(), wrap))
+    }
+
+    pub fn levels(&self) -> usize
+    {
+        self.levels.len()
+    }
+
+    fn texel(level: &MipLevel, wrap: WrapMode, x: i64, y: i64) -> Vec3<f64>
+    {
+        level.texels[wrap.apply(y, level.height) * level.width + wrap.apply(x, level.width)]
+    }
+
+    fn bilinear(&self, level: &MipLevel, u: f64, v: f64) -> Vec3<f64>
+    {
+        // texel centres sit at half integers
+        let s = u * level.width as f64 - 0.5;
+        let t = (1.0 - v) * level.height as f64 - 0.5;
+        let (x, y) = (s.floor(), t.floor());
+        let (fx, fy) = (s - x, t - y);
+        let (x, y) = (x as i64, y as i64);
+
+        let top = (1.0 - fx) * Self::texel(level, self.wrap, x, y) + fx * Self::texel(level, self.wrap, x + 1, y);
+        let bottom = (1.0 - fx) * Self::texel(level, self.wrap, x, y + 1) + fx * Self::texel(level, self.wrap, x + 1, y + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+
+    // filtered lookup: width is how much of the texture (in uv units) the lookup should cover,
+    // the two levels whose texels are closest to that size are read and blended (trilinear)
+    pub fn sample(&self, u: f64, v: f64, width: f64) -> Vec3<f64>
+    {
+        let base = self.levels[0].width.max(self.levels[0].height) as f64;
+        let lod = (width * base).max(1.0).log2().min((self.levels.len() - 1) as f64);
+
+        let fine = lod.floor() as usize;
+        let coarse = (fine + 1).min(self.levels.len() - 1);
+        let f = lod - fine as f64;
+        (1.0 - f) * self.bilinear(&self.levels[fine], u, v) + f * self.bilinear(&self.levels[coarse], u, v)
+    }
+}
+
+impl Texture for ImageTexture
+{
+    // with no footprint to go by, read the full resolution image
+    fn value(&self, u: f64, v: f64, _p: &Vec3<f64>) -> Vec3<f64>
+    {
+        self.sample(u, v, 0.0)
+    }
+
+    // the ray cone's footprint picks the level, so distant or grazing surfaces read pre-blurred texels
+    fn value_at(&self, hit_record: &HitRecord) -> Vec3<f64>
+    {
+        self.sample(hit_record.u, hit_record.v, hit_record.uv_width())
+    }
+}
+
+// which of n texels (and how much of each) make up each of m < n texels covering the same span:
+// texel i covers [i n / m, (i + 1) n / m), so odd sizes split the middle texel instead of reading
+// past the edge, where the wrap mode would blend in the opposite border
+fn box_weights(n: usize, m: usize) -> Vec<Vec<(usize, f64)>>
+{
+    let scale = n as f64 / m as f64;
+    (0..m).map(|i| {
+        let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+        (start.floor() as usize..(end.ceil() as usize).min(n))
+            .map(|j| (j, (end.min((j + 1) as f64) - start.max(j as f64)) / scale))
+            .filter(|&(_, w)| w > 0.0)
+            .collect()
+    }).collect()
+}
+
+// grey perlin fbm, scale sets how many noise features fit in a unit of space
+pub struct NoiseTexture
+{
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture
+{
+    const OCTAVES: u32 = 7;
+
+    pub fn new(scale: f64, seed: u64) -> Self
+    {
+        Self{noise: Perlin::new(seed), scale}
+    }
+}
+
+impl Texture for NoiseTexture
+{
+    fn value(&self, _u: f64, _v: f64, p: &Vec3<f64>) -> Vec3<f64>
+    {
+        let n = 0.5 * (1.0 + self.noise.fbm(&(self.scale * *p), Self::OCTAVES));
+        let n = n.clamp(0.0, 1.0);
+        Vec3::new(n, n, n)
+    }
+}
+
+// stripes along z bent by turbulence, the veins are where the sine dips
+pub struct Marble
+{
+    noise: Perlin,
+    scale: f64,
+    base: Vec3<f64>,
+    vein: Vec3<f64>,
+}
+
+impl Marble
+{
+    const OCTAVES: u32 = 7;
+    const DISTORTION: f64 = 10.0;
+
+    pub fn new(scale: f64, seed: u64) -> Self
+    {
+        Self::with_colors(scale, seed, Vec3::new(0.9, 0.9, 0.88), Vec3::new(0.25, 0.25, 0.3))
+    }
+
+    pub fn with_colors(scale: f64, seed: u64, base: Vec3<f64>, vein: Vec3<f64>) -> Self
+    {
+        Self{noise: Perlin::new(seed), scale, base, vein}
+    }
+}
+
+impl Texture for Marble
+{
+    fn value(&self, _u: f64, _v: f64, p: &Vec3<f64>) -> Vec3<f64>
+    {
+        let phase = self.scale * p.z() + Self::DISTORTION * self.noise.turbulence(p, Self::OCTAVES);
+        let t = 0.5 * (1.0 + phase.sin());
+
+        // sharpen the veins so most of the surface is the base colour
+        let vein = (1.0 - t).powi(4);
+        (1.0 - vein) * self.base + vein * self.vein
+    }
+}
+
+// growth rings around the y axis, rings per unit of radius set by scale, wobbled by low frequency noise
+pub struct Wood
+{
+    noise: Perlin,
+    scale: f64,
+    light: Vec3<f64>,
+    dark: Vec3<f64>,
+}
+
+impl Wood
+{
+    const OCTAVES: u32 = 4;
+    const DISTORTION: f64 = 0.6;
+
+    pub fn new(scale: f64, seed: u64) -> Self
+    {
+        Self::with_colors(scale, seed, Vec3::new(0.76, 0.55, 0.32), Vec3::new(0.42, 0.25, 0.11))
+    }
+
+    pub fn with_colors(scale: f64, seed: u64, light: Vec3<f64>, dark: Vec3<f64>) -> Self
+    {
+        Self{noise: Perlin::new(seed), scale, light, dark}
+    }
+}
+
+impl Texture for Wood
+{
+    fn value(&self, _u: f64, _v: f64, p: &Vec3<f64>) -> Vec3<f64>
+    {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = self.scale * radius + Self::DISTORTION * self.noise.fbm(p, Self::OCTAVES);
+
+        // a soft ring profile, squared so the dark late wood stays thin
+        let t = 0.5 - 0.5 * (2.0 * PI * rings).cos();
+        let t = t * t;
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn gray_image(width: usize, height: usize, values: &[f32]) -> Image
+    {
+        Image::new(width, height, values.iter().flat_map(|&v| [v, v, v]).collect())
+    }
+
+    fn mean(level: &MipLevel) -> f64
+    {
+        level.texels.iter().map(|t| *t.x()).sum::<f64>() / level.texels.len() as f64
+    }
+
+    #[test]
+    fn odd_sized_levels_do_not_wrap_around()
+    {
+        // a bright left column next to a dark right edge: with Repeat the old 2x2 average pulled the
+        // left column into the last texel of every odd level
+        let texture = ImageTexture::new(&gray_image(3, 1, &[1.0, 0.0, 0.0]), WrapMode::Repeat);
+        assert_eq!(texture.levels(), 3);
+
+        let level = &texture.levels[1];
+        assert_eq!((level.width, level.height), (2, 1));
+        assert!((level.texels[0].x() - 1.0 / 1.5).abs() < 1e-12);
+        assert_eq!(*level.texels[1].x(), 0.0);
+    }
+
+    #[test]
+    fn every_level_keeps_the_average()
+    {
+        let values: Vec<f32> = (0..35).map(|i| ((i * 7) % 11) as f32 / 10.0).collect();
+        let texture = ImageTexture::new(&gray_image(7, 5, &values), WrapMode::Clamp);
+
+        let expected = mean(&texture.levels[0]);
+        for level in texture.levels.iter()
+        {
+            assert!((mean(level) - expected).abs() < 1e-12, "{}x{} level is off", level.width, level.height);
+        }
+        let last = texture.levels.last().unwrap();
+        assert_eq!((last.width, last.height), (1, 1));
+    }
+
+    #[test]
+    fn the_footprint_picks_the_level()
+    {
+        let texture = ImageTexture::new(&gray_image(2, 2, &[1.0, 0.0, 0.0, 1.0]), WrapMode::Repeat);
+
+        // a unit square mapped to the whole image
+        let mut hit_record = HitRecord::default();
+        (hit_record.u, hit_record.v) = (0.25, 0.75);
+        hit_record.tangent = Vec3::new(1.0, 0.0, 0.0);
+        hit_record.bitangent = Vec3::new(0.0, 1.0, 0.0);
+
+        // a thin ray reads the top left texel, one covering the whole square reads the average
+        assert_eq!(*texture.value_at(&hit_record).x(), 1.0);
+        hit_record.footprint = 1.0;
+        assert!((texture.value_at(&hit_record).x() - 0.5).abs() < 1e-12);
+
+        // the footprint is measured on the surface, on a square twice the size the same cone covers half a texel
+        hit_record.tangent = Vec3::new(2.0, 0.0, 0.0);
+        hit_record.bitangent = Vec3::new(0.0, 2.0, 0.0);
+        assert_eq!(*texture.value_at(&hit_record).x(), 1.0);
+        hit_record.footprint = 2.0;
+        assert!((texture.value_at(&hit_record).x() - 0.5).abs() < 1e-12);
+
+        // and so does a ray meeting the surface at 60 degrees from the normal, which stretches the cone twofold
+        hit_record.footprint = 1.0;
+        assert_eq!(*texture.value_at(&hit_record).x(), 1.0);
+        hit_record.incidence = 0.5;
+        assert!((texture.value_at(&hit_record).x() - 0.5).abs() < 1e-12);
+    }
+}
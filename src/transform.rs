@@ -0,0 +1,145 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::{degrees_to_radians, gamma};
+
+use std::rc::Rc;
+
+// instances: instead of moving the object we move the ray into the object's space,
+// hit it there and then move the hit record back out into world space
+
+// both transforms can be animated, the start value applies at time 0 and the end value at time 1
+// (same convention as the moving sphere) so instanced geometry blurs with the camera shutter
+// the bounding boxes only cover times in [0, 1], which is why Camera keeps its shutter inside that range
+
+pub struct Translate {
+    object: Rc<dyn Hittable>,
+    offset: Ray,        // offset.at(time) is the displacement at that time
+}
+
+impl Translate {
+    pub fn new(object: Rc<dyn Hittable>, offset: Vec3<f64>) -> Self {
+        Self { object, offset: Ray::new(offset, Vec3::origin()) }
+    }
+
+    pub fn new_moving(object: Rc<dyn Hittable>, offset1: Vec3<f64>, offset2: Vec3<f64>) -> Self {
+        Self { object, offset: Ray::new(offset1, offset2 - offset1) }
+    }
+}
+
+impl Hittable for Translate {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let offset = self.offset.at(ray.time());
+
+        // move the ray backwards by the offset
+        let offset_ray = Ray::new_with_time(ray.origin() - &offset, *ray.direction(), ray.time());
+
+        if !self.object.hit(&offset_ray, interval, hit_record) {
+            return false;
+        }
+
+        // move the intersection point forwards by the offset
+        // moving the ray back and the point forward each round once, on top of the object's own error
+        let local = hit_record.point.abs();
+        hit_record.point += offset;
+        hit_record.error += gamma(1) * (local + hit_record.point.abs());
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.object.bounding_box();
+        let start = self.offset.at(0.0);
+        let end = self.offset.at(1.0);
+
+        let shifted = |offset: &Vec3<f64>| Aabb::from_points(&(bbox.min() + *offset), &(bbox.max() + *offset));
+        Aabb::surrounding(&shifted(&start), &shifted(&end))
+    }
+}
+
+pub struct RotateY {
+    object: Rc<dyn Hittable>,
+    angle1: f64,        // radians
+    angle2: f64,
+}
+
+impl RotateY {
+    // angle is in degrees, like the camera vfov
+    pub fn new(object: Rc<dyn Hittable>, angle: f64) -> Self {
+        Self::new_moving(object, angle, angle)
+    }
+
+    pub fn new_moving(object: Rc<dyn Hittable>, angle1: f64, angle2: f64) -> Self {
+        Self {
+            object,
+            angle1: degrees_to_radians(angle1),
+            angle2: degrees_to_radians(angle2),
+        }
+    }
+
+    // rotate v about the y axis by theta given as (sin, cos)
+    fn rotate(v: &Vec3<f64>, sin_theta: f64, cos_theta: f64) -> Vec3<f64> {
+        Vec3::new(
+            cos_theta * v.x() + sin_theta * v.z(),
+            *v.y(),
+            -sin_theta * v.x() + cos_theta * v.z(),
+        )
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let angle = self.angle1 + ray.time() * (self.angle2 - self.angle1);
+        let (sin_theta, cos_theta) = angle.sin_cos();
+
+        // transform the ray from world space to object space (rotate by -theta)
+        let origin = Self::rotate(ray.origin(), -sin_theta, cos_theta);
+        let direction = Self::rotate(ray.direction(), -sin_theta, cos_theta);
+        let rotated_ray = Ray::new_with_time(origin, direction, ray.time());
+
+        if !self.object.hit(&rotated_ray, interval, hit_record) {
+            return false;
+        }
+
+        // transform the intersection from object space back to world space
+        hit_record.point = Self::rotate(&hit_record.point, sin_theta, cos_theta);
+        hit_record.normal = Self::rotate(&hit_record.normal, sin_theta, cos_theta);
+        hit_record.geometric_normal = Self::rotate(&hit_record.geometric_normal, sin_theta, cos_theta);
+        hit_record.tangent = Self::rotate(&hit_record.tangent, sin_theta, cos_theta);
+        hit_record.bitangent = Self::rotate(&hit_record.bitangent, sin_theta, cos_theta);
+
+        // the error box becomes the box around the rotated box, plus rounding in the rotation itself (pbrt's rule)
+        let (s, c, e) = (sin_theta.abs(), cos_theta.abs(), hit_record.error);
+        let rotated_error = Vec3::new(c * e.x() + s * e.z(), *e.y(), s * e.x() + c * e.z());
+        hit_record.error = (1.0 + gamma(3)) * rotated_error + gamma(3) * hit_record.point.abs();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.object.bounding_box();
+        let (min, max) = (bbox.min(), bbox.max());
+
+        if self.angle1 != self.angle2 {
+            // while spinning the box can face any way, bound it by the circle its corners sweep around y
+            let rx = min.x().abs().max(max.x().abs());
+            let rz = min.z().abs().max(max.z().abs());
+            let r = (rx * rx + rz * rz).sqrt();
+            return Aabb::from_points(&Vec3::new(-r, *min.y(), -r), &Vec3::new(r, *max.y(), r));
+        }
+
+        // rotate all eight corners and take the box around them
+        let (sin_theta, cos_theta) = self.angle1.sin_cos();
+        let mut rotated = Aabb::empty();
+        for corner in 0..8 {
+            let p = Vec3::new(
+                if corner & 1 == 0 { *min.x() } else { *max.x() },
+                if corner & 2 == 0 { *min.y() } else { *max.y() },
+                if corner & 4 == 0 { *min.z() } else { *max.z() },
+            );
+            let q = Self::rotate(&p, sin_theta, cos_theta);
+            rotated = Aabb::surrounding(&rotated, &Aabb::from_points(&q, &q));
+        }
+        rotated
+    }
+}
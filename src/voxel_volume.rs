@@ -0,0 +1,546 @@
+use crate::aabb::Aabb;
+use crate::core::Vec3;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::random_canonical;
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::rc::Rc;
+
+// a dense 3d grid of values, either scalar (density) or rgb (albedo, emission)
+// voxel (i, j, k) is stored at ((k * ny + j) * nx + i) * channels, x varies fastest
+//
+// on disk a grid is a small text header followed by raw little endian f32s:
+//     VOXELGRID
+//     <nx> <ny> <nz> <channels>
+//     <nx * ny * nz * channels f32 values>
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    channels: usize,
+    data: Vec<f32>,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, channels: usize, data: Vec<f32>) -> Self {
+        assert!(nx > 0 && ny > 0 && nz > 0, "voxel grid needs at least one voxel per axis");
+        assert!(channels == 1 || channels == 3, "voxel grids hold 1 or 3 channels");
+        assert_eq!(data.len(), nx * ny * nz * channels, "voxel data does not match the grid size");
+
+        Self { nx, ny, nz, channels, data }
+    }
+
+    // build a scalar grid by evaluating f at every voxel centre, f gets coordinates in [0, 1]^3
+    pub fn from_fn(nx: usize, ny: usize, nz: usize, f: impl Fn(Vec3<f64>) -> f64) -> Self {
+        let mut data = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    data.push(f(Self::voxel_centre(i, j, k, nx, ny, nz)) as f32);
+                }
+            }
+        }
+        Self::new(nx, ny, nz, 1, data)
+    }
+
+    // same as from_fn but for rgb grids
+    pub fn from_fn_rgb(nx: usize, ny: usize, nz: usize, f: impl Fn(Vec3<f64>) -> Vec3<f64>) -> Self {
+        let mut data = Vec::with_capacity(nx * ny * nz * 3);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    let value = f(Self::voxel_centre(i, j, k, nx, ny, nz));
+                    data.push(*value.x() as f32);
+                    data.push(*value.y() as f32);
+                    data.push(*value.z() as f32);
+                }
+            }
+        }
+        Self::new(nx, ny, nz, 3, data)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    // the contents of a voxel grid file, split from load so it can be tested without touching the disk
+    pub(crate) fn parse(bytes: &[u8]) -> std::io::Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+
+        // the header is the first two lines
+        let first_newline = bytes.iter().position(|&b| b == b'\n').ok_or_else(|| invalid("missing voxel grid header"))?;
+        let second_newline = first_newline + 1 + bytes[first_newline + 1..].iter().position(|&b| b == b'\n').ok_or_else(|| invalid("missing voxel grid size line"))?;
+
+        let magic = std::str::from_utf8(&bytes[..first_newline]).map_err(|_| invalid("bad voxel grid header"))?;
+        if magic.trim() != "VOXELGRID" {
+            return Err(invalid("not a voxel grid file"));
+        }
+
+        let size_line = std::str::from_utf8(&bytes[first_newline + 1..second_newline]).map_err(|_| invalid("bad voxel grid size line"))?;
+        let sizes: Vec<usize> = size_line
+            .split_whitespace()
+            .map(|s| s.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("bad voxel grid size line"))?;
+
+        if sizes.len() != 4 || sizes[..3].contains(&0) || !(sizes[3] == 1 || sizes[3] == 3) {
+            return Err(invalid("voxel grid size line must be '<nx> <ny> <nz> <1 or 3>'"));
+        }
+        let (nx, ny, nz, channels) = (sizes[0], sizes[1], sizes[2], sizes[3]);
+
+        // the header is untrusted, refuse sizes whose byte count does not even fit in memory
+        let expected = nx.checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .and_then(|n| n.checked_mul(channels))
+            .and_then(|n| n.checked_mul(4))
+            .filter(|&bytes| bytes <= isize::MAX as usize)
+            .ok_or_else(|| invalid("voxel grid size overflows"))?;
+
+        let payload = &bytes[second_newline + 1..];
+        if payload.len() != expected {
+            return Err(invalid("voxel grid payload does not match its header"));
+        }
+
+        let data = payload
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+
+        Ok(Self::new(nx, ny, nz, channels, data))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut bytes = format!("VOXELGRID\n{} {} {} {}\n", self.nx, self.ny, self.nz, self.channels).into_bytes();
+        for value in self.data.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(path, bytes)
+    }
+
+    pub fn resolution(&self) -> (usize, usize, usize) {
+        (self.nx, self.ny, self.nz)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn voxel_centre(i: usize, j: usize, k: usize, nx: usize, ny: usize, nz: usize) -> Vec3<f64> {
+        Vec3::new(
+            (i as f64 + 0.5) / nx as f64,
+            (j as f64 + 0.5) / ny as f64,
+            (k as f64 + 0.5) / nz as f64,
+        )
+    }
+
+    fn voxel(&self, i: usize, j: usize, k: usize, channel: usize) -> f64 {
+        self.data[((k * self.ny + j) * self.nx + i) * self.channels + channel] as f64
+    }
+
+    // trilinear interpolation between voxel centres, p is in [0, 1]^3 and gets clamped at the borders
+    fn sample_channel(&self, p: &Vec3<f64>, channel: usize) -> f64 {
+        let split = |x: f64, n: usize| -> (usize, usize, f64) {
+            let x = (x * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i0 = x.floor() as usize;
+            let i1 = (i0 + 1).min(n - 1);
+            (i0, i1, x - i0 as f64)
+        };
+
+        let (i0, i1, fx) = split(*p.x(), self.nx);
+        let (j0, j1, fy) = split(*p.y(), self.ny);
+        let (k0, k1, fz) = split(*p.z(), self.nz);
+
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+
+        let c00 = lerp(self.voxel(i0, j0, k0, channel), self.voxel(i1, j0, k0, channel), fx);
+        let c10 = lerp(self.voxel(i0, j1, k0, channel), self.voxel(i1, j1, k0, channel), fx);
+        let c01 = lerp(self.voxel(i0, j0, k1, channel), self.voxel(i1, j0, k1, channel), fx);
+        let c11 = lerp(self.voxel(i0, j1, k1, channel), self.voxel(i1, j1, k1, channel), fx);
+
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+
+    pub fn sample_scalar(&self, p: &Vec3<f64>) -> f64 {
+        self.sample_channel(p, 0)
+    }
+
+    // scalar grids come back as a grey colour
+    pub fn sample(&self, p: &Vec3<f64>) -> Vec3<f64> {
+        if self.channels == 1 {
+            let v = self.sample_channel(p, 0);
+            return Vec3::new(v, v, v);
+        }
+        Vec3::new(
+            self.sample_channel(p, 0),
+            self.sample_channel(p, 1),
+            self.sample_channel(p, 2),
+        )
+    }
+}
+
+// coarse grid holding the maximum density of the region each cell covers
+// delta and ratio tracking step through it so empty space and thin regions cost few samples
+struct MajorantGrid {
+    res: [usize; 3],
+    values: Vec<f64>,
+}
+
+impl MajorantGrid {
+    const VOXELS_PER_CELL: usize = 8;
+
+    fn build(density: &VoxelGrid, scale: f64) -> Self {
+        let n = [density.nx, density.ny, density.nz];
+        let res = n.map(|n| n.div_ceil(Self::VOXELS_PER_CELL));
+        let mut values = Vec::with_capacity(res[0] * res[1] * res[2]);
+
+        // voxel index range touched by a majorant cell, one extra voxel each side because
+        // trilinear lookups near a cell border blend in the neighbouring voxels
+        let voxel_range = |cell: usize, axis: usize| {
+            let lo = (cell * n[axis] / res[axis]).saturating_sub(1);
+            let hi = ((cell + 1) * n[axis]).div_ceil(res[axis]).min(n[axis] - 1);
+            lo..=hi
+        };
+
+        for ck in 0..res[2] {
+            for cj in 0..res[1] {
+                for ci in 0..res[0] {
+                    let mut max_density: f64 = 0.0;
+                    for k in voxel_range(ck, 2) {
+                        for j in voxel_range(cj, 1) {
+                            for i in voxel_range(ci, 0) {
+                                max_density = max_density.max(density.voxel(i, j, k, 0));
+                            }
+                        }
+                    }
+                    values.push(max_density * scale);
+                }
+            }
+        }
+
+        Self { res, values }
+    }
+
+    fn lookup(&self, cell: [usize; 3]) -> f64 {
+        self.values[(cell[2] * self.res[1] + cell[1]) * self.res[0] + cell[0]]
+    }
+}
+
+// heterogeneous participating medium defined by voxel grids inside a world space box
+// distances are sampled with delta tracking and transmittance is estimated with ratio tracking,
+// both against the majorant grid, so the estimates are unbiased
+pub struct VoxelMedium {
+    bounds: Aabb,
+    density: Rc<VoxelGrid>,
+    density_scale: f64,
+    majorants: MajorantGrid,
+    phase_function: Rc<VoxelPhase>,
+}
+
+impl VoxelMedium {
+    // density_scale turns grid values into extinction per world unit
+    // a missing albedo grid means the medium only scatters, a missing emission grid means it emits nothing
+    pub fn new(bounds: Aabb, density: VoxelGrid, density_scale: f64, albedo: Option<VoxelGrid>, emission: Option<VoxelGrid>) -> Self {
+        let majorants = MajorantGrid::build(&density, density_scale);
+        let phase_function = Rc::new(VoxelPhase {
+            bounds,
+            albedo: albedo.map(Rc::new),
+            emission: emission.map(Rc::new),
+        });
+
+        Self {
+            bounds,
+            density: Rc::new(density),
+            density_scale,
+            majorants,
+            phase_function,
+        }
+    }
+
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // light given off per unit length at a world space point, black without an emission grid
+    pub fn emission(&self, point: &Vec3<f64>) -> Vec3<f64> {
+        self.phase_function.emission_at(point)
+    }
+
+    // probability that light travels along the ray between interval.min() and interval.max() without being
+    // absorbed or scattered, estimated by ratio tracking; meant for shadow rays through the volume
+    pub fn transmittance(&self, ray: &Ray, interval: &Interval) -> f64 {
+        let mut ray_t = *interval;
+        if !self.bounds.hit(ray, &mut ray_t) {
+            return 1.0;
+        }
+
+        let ray_length = ray.direction().mag();
+        let mut transmittance = 1.0;
+
+        self.traverse_majorants(ray, &ray_t, |sigma_maj, t_start, t_end| {
+            if sigma_maj <= 0.0 {
+                return true;
+            }
+
+            let mut t = t_start;
+            loop {
+                t -= (1.0 - random_canonical()).ln() / (sigma_maj * ray_length);
+                if t >= t_end {
+                    return true;
+                }
+
+                // each tentative collision multiplies in the chance of it being a null collision
+                transmittance *= 1.0 - self.sigma_t(&ray.at(t)) / sigma_maj;
+                if transmittance <= 0.0 {
+                    return false;
+                }
+            }
+        });
+
+        transmittance.max(0.0)
+    }
+
+    // extinction coefficient at a world space point
+    fn sigma_t(&self, point: &Vec3<f64>) -> f64 {
+        self.density.sample_scalar(&to_local(&self.bounds, point)) * self.density_scale
+    }
+
+    // walk the majorant cells the ray passes through between ray_t.min() and ray_t.max() (3d dda)
+    // visit gets (majorant, cell entry t, cell exit t) and returns false to stop early
+    fn traverse_majorants(&self, ray: &Ray, ray_t: &Interval, mut visit: impl FnMut(f64, f64, f64) -> bool) {
+        let res = self.majorants.res;
+        let origin = to_local(&self.bounds, ray.origin());
+        let extent = self.bounds.max() - self.bounds.min();
+
+        // position and direction in majorant cell units
+        let mut pos = [0.0; 3];
+        let mut dir = [0.0; 3];
+        for axis in 0..3 {
+            dir[axis] = ray.direction().axis(axis) / extent.axis(axis) * res[axis] as f64;
+            pos[axis] = (origin.axis(axis) + ray.direction().axis(axis) / extent.axis(axis) * ray_t.min()) * res[axis] as f64;
+        }
+
+        let mut cell = [0usize; 3];
+        let mut next_t = [f64::INFINITY; 3];
+        let mut delta_t = [f64::INFINITY; 3];
+        let mut step = [0i64; 3];
+
+        for axis in 0..3 {
+            cell[axis] = (pos[axis].floor().max(0.0) as usize).min(res[axis] - 1);
+
+            if dir[axis] > 0.0 {
+                next_t[axis] = ray_t.min() + ((cell[axis] + 1) as f64 - pos[axis]) / dir[axis];
+                delta_t[axis] = 1.0 / dir[axis];
+                step[axis] = 1;
+            } else if dir[axis] < 0.0 {
+                next_t[axis] = ray_t.min() + (cell[axis] as f64 - pos[axis]) / dir[axis];
+                delta_t[axis] = -1.0 / dir[axis];
+                step[axis] = -1;
+            }
+        }
+
+        let mut t = ray_t.min();
+        loop {
+            let axis = if next_t[0] < next_t[1] {
+                if next_t[0] < next_t[2] { 0 } else { 2 }
+            } else if next_t[1] < next_t[2] {
+                1
+            } else {
+                2
+            };
+
+            let t_exit = next_t[axis].min(ray_t.max());
+            if !visit(self.majorants.lookup(cell), t, t_exit) || next_t[axis] >= ray_t.max() {
+                return;
+            }
+
+            let next_cell = cell[axis] as i64 + step[axis];
+            if next_cell < 0 || next_cell >= res[axis] as i64 {
+                return;
+            }
+
+            cell[axis] = next_cell as usize;
+            t = next_t[axis];
+            next_t[axis] += delta_t[axis];
+        }
+    }
+}
+
+impl Hittable for VoxelMedium {
+    fn hit(&self, ray: &Ray, interval: &Interval, hit_record: &mut HitRecord) -> bool {
+        let mut ray_t = *interval;
+        if !self.bounds.hit(ray, &mut ray_t) {
+            return false;
+        }
+
+        let ray_length = ray.direction().mag();
+        let mut collision = None;
+
+        // delta tracking: sample tentative collisions against the majorant and accept each one
+        // as real with probability sigma_t / sigma_maj, rejected ones are null collisions
+        self.traverse_majorants(ray, &ray_t, |sigma_maj, t_start, t_end| {
+            if sigma_maj <= 0.0 {
+                return true;
+            }
+
+            let mut t = t_start;
+            loop {
+                t -= (1.0 - random_canonical()).ln() / (sigma_maj * ray_length);
+
+                // exponential steps are memoryless, so leaving the cell just restarts in the next one
+                if t >= t_end {
+                    return true;
+                }
+
+                if random_canonical() * sigma_maj < self.sigma_t(&ray.at(t)) {
+                    collision = Some(t);
+                    return false;
+                }
+            }
+        });
+
+        let Some(t) = collision else {
+            return false;
+        };
+
+        hit_record.t = t;
+        hit_record.point = ray.at(t);
+        hit_record.error = Vec3::origin();
+
+        // same as the constant medium, the normal and facing are arbitrary inside a volume
+        hit_record.normal = Vec3::new(1.0, 0.0, 0.0);
+        hit_record.geometric_normal = hit_record.normal;
+        hit_record.front_face = true;
+        hit_record.mat = self.phase_function.clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+// isotropic phase function that looks up albedo and emission at the collision point
+struct VoxelPhase {
+    bounds: Aabb,
+    albedo: Option<Rc<VoxelGrid>>,
+    emission: Option<Rc<VoxelGrid>>,
+}
+
+impl VoxelPhase {
+    fn albedo_at(&self, point: &Vec3<f64>) -> Vec3<f64> {
+        match &self.albedo {
+            Some(grid) => grid.sample(&to_local(&self.bounds, point)),
+            None => Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    // emission comes from the absorbing part of the medium, so it is weighted by (1 - albedo)
+    fn emission_at(&self, point: &Vec3<f64>) -> Vec3<f64> {
+        match &self.emission {
+            Some(grid) => {
+                let absorption = Vec3::new(1.0, 1.0, 1.0) - self.albedo_at(point);
+                absorption * grid.sample(&to_local(&self.bounds, point))
+            }
+            None => Vec3::origin(),
+        }
+    }
+}
+
+impl Material for VoxelPhase {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, attenuation: &mut Vec3<f64>, scattered: &mut Ray) -> bool {
+        *scattered = hit_record.spawn_ray(Vec3::random_unit_vector(), ray_in.time());
+        *attenuation = self.albedo_at(&hit_record.point);
+        true
+    }
+
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Vec3<f64> {
+        self.emission_at(&hit_record.point)
+    }
+}
+
+// map a world space point into the [0, 1]^3 coordinates of the box
+fn to_local(bounds: &Aabb, point: &Vec3<f64>) -> Vec3<f64> {
+    let min = bounds.min();
+    let extent = bounds.max() - min;
+    Vec3::new(
+        (point.x() - min.x()) / extent.x(),
+        (point.y() - min.y()) / extent.y(),
+        (point.z() - min.z()) / extent.z(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_file(size_line: &str, values: &[f32]) -> Vec<u8> {
+        let mut bytes = format!("VOXELGRID\n{}\n", size_line).into_bytes();
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_reads_x_fastest() {
+        let grid = VoxelGrid::parse(&grid_file("2 1 2 1", &[0.0, 1.0, 2.0, 3.0])).unwrap();
+        assert_eq!(grid.resolution(), (2, 1, 2));
+        assert_eq!(grid.channels(), 1);
+        assert_eq!(grid.voxel(1, 0, 0, 0), 1.0);
+        assert_eq!(grid.voxel(0, 0, 1, 0), 2.0);
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let grid = VoxelGrid::from_fn_rgb(3, 2, 1, |p| p * 2.0);
+        let path = std::env::temp_dir().join(format!("tracer-voxel-test-{}.vox", std::process::id()));
+        grid.save(&path).unwrap();
+        let loaded = VoxelGrid::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.resolution(), loaded.channels()), ((3, 2, 1), 3));
+        assert_eq!(loaded.data, grid.data);
+    }
+
+    #[test]
+    fn malformed_grid_is_an_error() {
+        assert!(VoxelGrid::parse(b"VOXELGRID").is_err());
+        assert!(VoxelGrid::parse(&grid_file("2 1 1", &[0.0, 0.0])).is_err());
+        assert!(VoxelGrid::parse(&grid_file("1 1 1 2", &[0.0, 0.0])).is_err());
+        assert!(VoxelGrid::parse(&grid_file("0 1 1 1", &[])).is_err());
+        assert!(VoxelGrid::parse(&grid_file("2 1 1 1", &[0.0])).is_err());
+        assert!(VoxelGrid::parse(&grid_file("4294967296 4294967296 1 1", &[0.0])).is_err());
+        assert!(VoxelGrid::parse(&grid_file("18446744073709551615 2 1 1", &[0.0])).is_err());
+
+        let mut wrong_magic = grid_file("1 1 1 1", &[0.0]);
+        wrong_magic[0] = b'W';
+        assert!(VoxelGrid::parse(&wrong_magic).is_err());
+    }
+
+    #[test]
+    fn ratio_tracking_matches_beer_lambert_in_a_constant_medium() {
+        let sigma = 0.7;
+        let bounds = Aabb::from_points(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(2.0, 1.0, 1.0));
+        let medium = VoxelMedium::new(bounds, VoxelGrid::from_fn(16, 8, 8, |_| 1.0), sigma, None, None);
+
+        // along x through the middle of the box, 2 units of medium
+        let ray = Ray::new(Vec3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let samples = 20_000;
+        let mean = (0..samples).map(|_| medium.transmittance(&ray, &Interval::new(0.0, f64::INFINITY))).sum::<f64>() / samples as f64;
+
+        // each estimate is 0 or 1 here, so the standard error is under 0.004
+        let expected = (-sigma * 2.0f64).exp();
+        assert!((mean - expected).abs() < 0.02, "mean transmittance {} against {}", mean, expected);
+
+        // a ray that misses the box sees no medium at all
+        let miss = Ray::new(Vec3::new(-1.0, 2.0, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(medium.transmittance(&miss, &Interval::new(0.0, f64::INFINITY)), 1.0);
+    }
+}